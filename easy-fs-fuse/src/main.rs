@@ -0,0 +1,141 @@
+// 用法：
+//   easy-fs-fuse pack <镜像> <用户程序所在目录> <程序名>...
+// pack 将各用户程序写入新建镜像的根目录，写完后重新打开镜像逐一校验
+// 文件系统本身的测试在 easy-fs 中，用 cargo test 运行
+use easy_fs::{
+    block_cache_clear, block_cache_sync_all, BlockDevice, EasyFileSystem, FsError, IoError,
+    BLOCK_SIZE,
+};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::process;
+use std::sync::{Arc, Mutex};
+
+// 镜像大小：16 MiB
+const IMAGE_BLOCKS: usize = 16 * 2048;
+// inode 位图的块数，最多 4096 个文件
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+// 以宿主机上的文件作为块设备
+struct BlockFile {
+    file: Mutex<File>,
+    blocks: usize,
+}
+
+impl BlockDevice for BlockFile {
+    fn block_count(&self) -> usize {
+        self.blocks
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), IoError> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .and_then(|_| file.read_exact(buf))
+            .map_err(|_| IoError)
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), IoError> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .and_then(|_| file.write_all(buf))
+            .map_err(|_| IoError)
+    }
+}
+
+// 新建镜像，原有内容会被清空
+fn create_image(path: &str) -> Arc<dyn BlockDevice> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap_or_else(|err| fail(&format!("failed to open {}: {}", path, err)));
+    file.set_len((IMAGE_BLOCKS * BLOCK_SIZE) as u64).unwrap();
+    Arc::new(BlockFile {
+        file: Mutex::new(file),
+        blocks: IMAGE_BLOCKS,
+    })
+}
+
+// 打开已有的镜像
+fn open_image(path: &str) -> Arc<dyn BlockDevice> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap_or_else(|err| fail(&format!("failed to open {}: {}", path, err)));
+    Arc::new(BlockFile {
+        file: Mutex::new(file),
+        blocks: IMAGE_BLOCKS,
+    })
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("easy-fs-fuse: {}", message);
+    process::exit(1);
+}
+
+fn io_error(_: IoError) -> ! {
+    fail("I/O error on the image")
+}
+
+fn usage() -> ! {
+    fail("usage: easy-fs-fuse pack <image> <dir> <name>...")
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("pack") if args.len() >= 4 => pack(&args[2], &args[3], &args[4..]),
+        _ => usage(),
+    }
+}
+
+fn pack(image: &str, dir: &str, names: &[String]) {
+    let block_device = create_image(image);
+    let efs = EasyFileSystem::create(block_device, IMAGE_BLOCKS as u32, INODE_BITMAP_BLOCKS)
+        .unwrap_or_else(|err| io_error(err));
+    let root = EasyFileSystem::root_inode(&efs);
+    let mut programs = Vec::new();
+    for name in names {
+        let path = format!("{}/{}", dir, name);
+        let mut data = Vec::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .unwrap_or_else(|err| fail(&format!("failed to read {}: {}", path, err)));
+        let inode = match root.create(name) {
+            Ok(Some(inode)) => inode,
+            Ok(None) => fail(&format!("duplicated file {}", name)),
+            Err(FsError::NameTooLong) => fail(&format!("file name {} is too long", name)),
+            Err(FsError::Io) => io_error(IoError),
+        };
+        if inode.write_at(0, &data).unwrap_or_else(|err| io_error(err)) != Some(data.len()) {
+            fail(&format!("no space left for {}", name));
+        }
+        println!("packed {} ({} bytes)", name, data.len());
+        programs.push((name, data));
+    }
+    block_cache_sync_all().unwrap_or_else(|err| io_error(err));
+
+    // 校验：丢弃块缓存，重新打开镜像文件，读出的内容应与原文件一致
+    drop(root);
+    drop(efs);
+    block_cache_clear().unwrap_or_else(|err| io_error(err));
+    let efs = EasyFileSystem::open(open_image(image))
+        .unwrap_or_else(|| fail("failed to reopen the image"));
+    let root = EasyFileSystem::root_inode(&efs);
+    let files = root.ls().unwrap_or_else(|err| io_error(err));
+    assert_eq!(files.len(), programs.len());
+    for (name, data) in programs.iter() {
+        let inode = root
+            .find(name)
+            .unwrap_or_else(|err| io_error(err))
+            .unwrap_or_else(|| fail(&format!("{} is missing", name)));
+        if inode.read_all().unwrap_or_else(|err| io_error(err)) != *data {
+            fail(&format!("{} is corrupted", name));
+        }
+    }
+    println!("{}: {} files verified", image, programs.len());
+}
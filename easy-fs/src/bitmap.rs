@@ -0,0 +1,67 @@
+use super::block_cache::get_block_cache;
+use super::{BlockDevice, IoError, BLOCK_SIZE};
+use alloc::sync::Arc;
+
+type BitmapBlock = [u64; BLOCK_SIZE / 8];
+
+// 每个块中的位数
+const BLOCK_BITS: usize = BLOCK_SIZE * 8;
+
+// 从 start_block_id 开始的 blocks 个块组成的位图
+pub struct Bitmap {
+    start_block_id: usize,
+    blocks: usize,
+}
+
+// 位号 -> (块号, 块内的 u64 下标, u64 内的位)
+fn decomposition(mut bit: usize) -> (usize, usize, usize) {
+    let block_pos = bit / BLOCK_BITS;
+    bit %= BLOCK_BITS;
+    (block_pos, bit / 64, bit % 64)
+}
+
+impl Bitmap {
+    pub fn new(start_block_id: usize, blocks: usize) -> Self {
+        Bitmap {
+            start_block_id,
+            blocks,
+        }
+    }
+
+    // 分配一个位，返回其位号，所有位都已分配时返回 None
+    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Result<Option<usize>, IoError> {
+        for block_id in 0..self.blocks {
+            let pos = get_block_cache(block_id + self.start_block_id, block_device.clone())?
+                .lock()
+                .modify(0, |bitmap_block: &mut BitmapBlock| {
+                    let (bits64_pos, bits64) = bitmap_block
+                        .iter()
+                        .enumerate()
+                        .find(|(_, bits64)| **bits64 != u64::MAX)?;
+                    let inner_pos = (!*bits64).trailing_zeros() as usize;
+                    bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+                    Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos)
+                });
+            if pos.is_some() {
+                return Ok(pos);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) -> Result<(), IoError> {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, block_device.clone())?
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                assert!(bitmap_block[bits64_pos] & (1u64 << inner_pos) != 0);
+                bitmap_block[bits64_pos] -= 1u64 << inner_pos;
+            });
+        Ok(())
+    }
+
+    // 位图能表示的最大位数
+    pub fn maximum(&self) -> usize {
+        self.blocks * BLOCK_BITS
+    }
+}
@@ -0,0 +1,137 @@
+use super::{BlockDevice, IoError, BLOCK_SIZE};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+// 块缓存的数据按 8 字节对齐，可以直接当作磁盘上的各种结构访问
+#[repr(C, align(8))]
+struct CacheData([u8; BLOCK_SIZE]);
+
+// 内存中一个块的副本，被修改过的块在换出或 sync 时写回
+pub struct BlockCache {
+    cache: CacheData,
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+    modified: bool,
+}
+
+impl BlockCache {
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Result<Self, IoError> {
+        let mut cache = CacheData([0; BLOCK_SIZE]);
+        block_device.read_block(block_id, &mut cache.0)?;
+        Ok(BlockCache {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        })
+    }
+
+    fn addr_of_offset(&self, offset: usize) -> usize {
+        &self.cache.0[offset] as *const _ as usize
+    }
+
+    // 将块中 offset 处的数据视为类型 T
+    pub fn get_ref<T: Sized>(&self, offset: usize) -> &T {
+        assert!(offset + core::mem::size_of::<T>() <= BLOCK_SIZE);
+        unsafe { &*(self.addr_of_offset(offset) as *const T) }
+    }
+
+    pub fn get_mut<T: Sized>(&mut self, offset: usize) -> &mut T {
+        assert!(offset + core::mem::size_of::<T>() <= BLOCK_SIZE);
+        self.modified = true;
+        unsafe { &mut *(self.addr_of_offset(offset) as *mut T) }
+    }
+
+    pub fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get_ref(offset))
+    }
+
+    pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    // 写回失败时仍保留修改标记，之后还会再次尝试
+    pub fn sync(&mut self) -> Result<(), IoError> {
+        if self.modified {
+            self.block_device
+                .write_block(self.block_id, &self.cache.0)?;
+            self.modified = false;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BlockCache {
+    // 此时已经无法报告错误，换出与 sync 时会先行写回
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+// 最多同时缓存的块数
+const BLOCK_CACHE_SIZE: usize = 16;
+
+// 以块号区分缓存，因此同一时间只能使用一个块设备
+struct BlockCacheManager {
+    queue: Vec<(usize, Arc<Mutex<BlockCache>>)>,
+}
+
+impl BlockCacheManager {
+    const fn new() -> Self {
+        BlockCacheManager { queue: Vec::new() }
+    }
+
+    fn get_block_cache(
+        &mut self,
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Result<Arc<Mutex<BlockCache>>, IoError> {
+        if let Some((_, cache)) = self.queue.iter().find(|(id, _)| *id == block_id) {
+            return Ok(cache.clone());
+        }
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            // 换出最早加入且没有被使用的块，写回失败时保留在缓存中
+            let index = self
+                .queue
+                .iter()
+                .position(|(_, cache)| Arc::strong_count(cache) == 1)
+                .expect("run out of block cache!");
+            self.queue[index].1.lock().sync()?;
+            self.queue.remove(index);
+        }
+        let cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)?));
+        self.queue.push((block_id, cache.clone()));
+        Ok(cache)
+    }
+}
+
+static BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> = Mutex::new(BlockCacheManager::new());
+
+// 块不在缓存中时从块设备读入，读入失败时返回 IoError
+pub fn get_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Result<Arc<Mutex<BlockCache>>, IoError> {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .get_block_cache(block_id, block_device)
+}
+
+// 将所有修改过的块写回块设备，有块写回失败时返回 IoError
+pub fn block_cache_sync_all() -> Result<(), IoError> {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    let mut result = Ok(());
+    for (_, cache) in manager.queue.iter() {
+        result = result.and(cache.lock().sync());
+    }
+    result
+}
+
+// 写回并丢弃所有缓存的块，之后的访问都会重新读取块设备
+// 切换块设备之前必须调用
+pub fn block_cache_clear() -> Result<(), IoError> {
+    let result = block_cache_sync_all();
+    BLOCK_CACHE_MANAGER.lock().queue.clear();
+    result
+}
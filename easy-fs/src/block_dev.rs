@@ -0,0 +1,12 @@
+// 块设备读写失败，例如磁盘返回了错误状态，或者块号超出范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoError;
+
+// 块设备接口，内核中由磁盘驱动实现，宿主机上由镜像文件实现
+pub trait BlockDevice: Send + Sync {
+    // 块的个数
+    fn block_count(&self) -> usize;
+    // buf 的长度必须为 BLOCK_SIZE
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), IoError>;
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), IoError>;
+}
@@ -0,0 +1,169 @@
+use super::bitmap::Bitmap;
+use super::block_cache::{block_cache_sync_all, get_block_cache};
+use super::layout::{DiskInode, DiskInodeType, SuperBlock};
+use super::vfs::Inode;
+use super::{BlockDevice, IoError, BLOCK_SIZE};
+use alloc::sync::Arc;
+use spin::Mutex;
+
+type DataBlock = [u8; BLOCK_SIZE];
+
+pub struct EasyFileSystem {
+    pub block_device: Arc<dyn BlockDevice>,
+    pub inode_bitmap: Bitmap,
+    pub data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+    // 数据位图的最后一个块中可能有多余的位，不能超过数据区的块数
+    data_area_blocks: u32,
+}
+
+impl EasyFileSystem {
+    // 在块设备上新建文件系统，只有一个空的根目录
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Result<Arc<Mutex<Self>>, IoError> {
+        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
+        let inode_num = inode_bitmap.maximum();
+        #[allow(clippy::manual_div_ceil)]
+        let inode_area_blocks =
+            ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SIZE - 1) / BLOCK_SIZE) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        // 每个位图块管理 BLOCK_SIZE * 8 个数据块
+        let bits = BLOCK_SIZE as u32 * 8;
+        let data_bitmap_blocks = (data_total_blocks + bits) / (bits + 1);
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        let data_bitmap = Bitmap::new(
+            (1 + inode_total_blocks) as usize,
+            data_bitmap_blocks as usize,
+        );
+        let mut efs = EasyFileSystem {
+            block_device: block_device.clone(),
+            inode_bitmap,
+            data_bitmap,
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks,
+        };
+        // 清空所有块
+        for i in 0..total_blocks {
+            get_block_cache(i as usize, block_device.clone())?
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    data_block.iter_mut().for_each(|byte| *byte = 0);
+                });
+        }
+        get_block_cache(0, block_device.clone())?.lock().modify(
+            0,
+            |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks,
+                    data_area_blocks,
+                );
+            },
+        );
+        // 0 号 inode 为根目录
+        assert_eq!(efs.alloc_inode()?, Some(0));
+        let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
+        get_block_cache(root_inode_block_id as usize, block_device.clone())?
+            .lock()
+            .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::Directory);
+            });
+        block_cache_sync_all()?;
+        Ok(Arc::new(Mutex::new(efs)))
+    }
+
+    // 打开块设备上已有的文件系统，读取超级块失败或超级块无效时返回 None
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
+        get_block_cache(0, block_device.clone())
+            .ok()?
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                if !super_block.is_valid() {
+                    return None;
+                }
+                let inode_total_blocks =
+                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                let efs = EasyFileSystem {
+                    block_device: block_device.clone(),
+                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
+                    data_bitmap: Bitmap::new(
+                        (1 + inode_total_blocks) as usize,
+                        super_block.data_bitmap_blocks as usize,
+                    ),
+                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    data_area_blocks: super_block.data_area_blocks,
+                };
+                Some(Arc::new(Mutex::new(efs)))
+            })
+    }
+
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        let block_device = efs.lock().block_device.clone();
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        Inode::new(block_id, block_offset, efs.clone(), block_device)
+    }
+
+    // inode_id 号 inode 所在的块号与块内偏移
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SIZE / inode_size) as u32;
+        let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
+        (
+            block_id,
+            (inode_id % inodes_per_block) as usize * inode_size,
+        )
+    }
+
+    pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
+        self.data_area_start_block + data_block_id
+    }
+
+    // inode 用完时返回 None
+    pub fn alloc_inode(&mut self) -> Result<Option<u32>, IoError> {
+        Ok(self
+            .inode_bitmap
+            .alloc(&self.block_device)?
+            .map(|inode_id| inode_id as u32))
+    }
+
+    pub fn dealloc_inode(&mut self, inode_id: u32) -> Result<(), IoError> {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize)
+    }
+
+    // 返回分配的块号，数据块在回收时已经清零，数据块用完时返回 None
+    pub fn alloc_data(&mut self) -> Result<Option<u32>, IoError> {
+        let block_id = match self.data_bitmap.alloc(&self.block_device)? {
+            Some(block_id) => block_id as u32,
+            None => return Ok(None),
+        };
+        // 位图总是分配最小的空闲位，超出数据区说明数据区已满
+        if block_id >= self.data_area_blocks {
+            self.data_bitmap
+                .dealloc(&self.block_device, block_id as usize)?;
+            return Ok(None);
+        }
+        Ok(Some(block_id + self.data_area_start_block))
+    }
+
+    pub fn dealloc_data(&mut self, block_id: u32) -> Result<(), IoError> {
+        get_block_cache(block_id as usize, self.block_device.clone())?
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                data_block.iter_mut().for_each(|byte| *byte = 0);
+            });
+        self.data_bitmap.dealloc(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+        )
+    }
+}
@@ -0,0 +1,411 @@
+use super::block_cache::get_block_cache;
+use super::{BlockDevice, IoError, BLOCK_SIZE};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+const EFS_MAGIC: u32 = 0x3b80_0001;
+// 一个 DiskInode 恰好占 128 字节
+const INODE_DIRECT_COUNT: usize = 28;
+// 文件名最长 27 字节，末尾留一个 '\0'
+// 文件名的最大字节数
+pub const NAME_LENGTH_LIMIT: usize = 27;
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SIZE / 4;
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+// 直接与间接索引所能覆盖的最大文件大小
+pub const MAX_FILE_SIZE: usize = INDIRECT2_BOUND * BLOCK_SIZE;
+
+// 位于 0 号块，记录其余各区域的大小
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    pub total_blocks: u32,
+    pub inode_bitmap_blocks: u32,
+    pub inode_area_blocks: u32,
+    pub data_bitmap_blocks: u32,
+    pub data_area_blocks: u32,
+}
+
+impl SuperBlock {
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = SuperBlock {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+// 一级索引块中保存的是数据块号
+type IndirectBlock = [u32; BLOCK_SIZE / 4];
+type DataBlock = [u8; BLOCK_SIZE];
+
+// 文件内容依次保存在直接索引、一级间接索引与二级间接索引指向的数据块中
+#[repr(C)]
+pub struct DiskInode {
+    pub size: u32,
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    pub indirect1: u32,
+    pub indirect2: u32,
+    type_: DiskInodeType,
+}
+
+impl DiskInode {
+    // 新的 inode 不占用任何数据块
+    pub fn initialize(&mut self, type_: DiskInodeType) {
+        self.size = 0;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.type_ = type_;
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.type_ == DiskInodeType::File
+    }
+
+    // 保存内容所需的数据块数
+    pub fn data_blocks(&self) -> u32 {
+        Self::_data_blocks(self.size)
+    }
+
+    // 工具链中的 u32 还没有 div_ceil
+    #[allow(clippy::manual_div_ceil)]
+    fn _data_blocks(size: u32) -> u32 {
+        (size + BLOCK_SIZE as u32 - 1) / BLOCK_SIZE as u32
+    }
+
+    // 大小为 size 时所需的数据块与索引块总数
+    #[allow(clippy::manual_div_ceil)]
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
+        let mut total = data_blocks;
+        if data_blocks > INODE_DIRECT_COUNT {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            total += 1;
+            total +=
+                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+        }
+        total as u32
+    }
+
+    // 扩大到 new_size 需要新分配的块数
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    // 文件中第 inner_id 个数据块的块号
+    pub fn get_block_id(
+        &self,
+        inner_id: u32,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Result<u32, IoError> {
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            Ok(self.direct[inner_id])
+        } else if inner_id < INDIRECT1_BOUND {
+            Ok(
+                get_block_cache(self.indirect1 as usize, block_device.clone())?
+                    .lock()
+                    .read(0, |indirect_block: &IndirectBlock| {
+                        indirect_block[inner_id - INODE_DIRECT_COUNT]
+                    }),
+            )
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = get_block_cache(self.indirect2 as usize, block_device.clone())?
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            Ok(get_block_cache(indirect1 as usize, block_device.clone())?
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT]
+                }))
+        }
+    }
+
+    // 扩大到 new_size，new_blocks 为调用者分配好的 blocks_num_needed 个块
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Result<(), IoError> {
+        let mut current_blocks = self.data_blocks();
+        self.size = new_size;
+        let mut total_blocks = self.data_blocks();
+        let mut new_blocks = new_blocks.into_iter();
+        // 直接索引
+        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
+            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+        // 一级间接索引块
+        if total_blocks > INODE_DIRECT_COUNT as u32 {
+            if current_blocks == INODE_DIRECT_COUNT as u32 {
+                self.indirect1 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_DIRECT_COUNT as u32;
+            total_blocks -= INODE_DIRECT_COUNT as u32;
+        } else {
+            return Ok(());
+        }
+        get_block_cache(self.indirect1 as usize, block_device.clone())?
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
+                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            });
+        // 二级间接索引块
+        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
+            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
+                self.indirect2 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT1_COUNT as u32;
+            total_blocks -= INODE_INDIRECT1_COUNT as u32;
+        } else {
+            return Ok(());
+        }
+        // 从 (a0, b0) 填到 (a1, b1)
+        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
+        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
+        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
+        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect2 as usize, block_device.clone())?
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                while a0 < a1 || (a0 == a1 && b0 < b1) {
+                    if b0 == 0 {
+                        indirect2[a0] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect2[a0] as usize, block_device.clone())?
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            indirect1[b0] = new_blocks.next().unwrap();
+                        });
+                    b0 += 1;
+                    if b0 == INODE_INDIRECT1_COUNT {
+                        b0 = 0;
+                        a0 += 1;
+                    }
+                }
+                Ok(())
+            })
+    }
+
+    // 清空文件内容，返回需要回收的数据块与索引块
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Result<Vec<u32>, IoError> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        let mut current_blocks = 0usize;
+        // 直接索引
+        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
+            v.push(self.direct[current_blocks]);
+            current_blocks += 1;
+        }
+        // 一级间接索引块
+        if data_blocks > INODE_DIRECT_COUNT {
+            v.push(self.indirect1);
+            data_blocks -= INODE_DIRECT_COUNT;
+            current_blocks = 0;
+            get_block_cache(self.indirect1 as usize, block_device.clone())?
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
+                        v.push(indirect1[current_blocks]);
+                        current_blocks += 1;
+                    }
+                });
+        }
+        // 二级间接索引块
+        if data_blocks > INODE_INDIRECT1_COUNT {
+            v.push(self.indirect2);
+            data_blocks -= INODE_INDIRECT1_COUNT;
+            assert!(data_blocks <= INODE_INDIRECT2_COUNT);
+            let a1 = data_blocks / INODE_INDIRECT1_COUNT;
+            let b1 = data_blocks % INODE_INDIRECT1_COUNT;
+            get_block_cache(self.indirect2 as usize, block_device.clone())?
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    // 已经填满的一级索引块
+                    for entry in indirect2.iter().take(a1) {
+                        v.push(*entry);
+                        get_block_cache(*entry as usize, block_device.clone())?
+                            .lock()
+                            .read(0, |indirect1: &IndirectBlock| {
+                                v.extend(indirect1.iter());
+                            });
+                    }
+                    // 最后一个未填满的一级索引块
+                    if b1 > 0 {
+                        v.push(indirect2[a1]);
+                        get_block_cache(indirect2[a1] as usize, block_device.clone())?
+                            .lock()
+                            .read(0, |indirect1: &IndirectBlock| {
+                                v.extend(indirect1.iter().take(b1));
+                            });
+                    }
+                    Ok(())
+                })?;
+        }
+        // 所有索引块都读取成功之后才修改 inode，失败时文件保持原样
+        self.size = 0;
+        self.direct = [0; INODE_DIRECT_COUNT];
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        Ok(v)
+    }
+
+    // 从 offset 开始读入 buf，返回实际读入的字节数
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Result<usize, IoError> {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        if start >= end {
+            return Ok(0);
+        }
+        let mut start_block = start / BLOCK_SIZE;
+        let mut read_size = 0usize;
+        loop {
+            // 当前块的结束位置
+            let end_current_block = ((start / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device)? as usize,
+                block_device.clone(),
+            )?
+            .lock()
+            .read(0, |data_block: &DataBlock| {
+                let src = &data_block[start % BLOCK_SIZE..start % BLOCK_SIZE + block_read_size];
+                dst.copy_from_slice(src);
+            });
+            read_size += block_read_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        Ok(read_size)
+    }
+
+    // 调用者需保证 size 已经足够大
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> Result<usize, IoError> {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        assert!(start <= end);
+        let mut start_block = start / BLOCK_SIZE;
+        let mut write_size = 0usize;
+        loop {
+            let end_current_block = ((start / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let block_write_size = end_current_block - start;
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device)? as usize,
+                block_device.clone(),
+            )?
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst =
+                    &mut data_block[start % BLOCK_SIZE..start % BLOCK_SIZE + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            write_size += block_write_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        Ok(write_size)
+    }
+}
+
+// 目录的内容是若干目录项
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+pub const DIRENT_SIZE: usize = 32;
+
+impl DirEntry {
+    pub fn empty() -> Self {
+        DirEntry {
+            name: [0u8; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    // name 不能超过 NAME_LENGTH_LIMIT，截断可能把多字节字符从中间切开
+    pub fn new(name: &str, inode_number: u32) -> Self {
+        assert!(name.len() <= NAME_LENGTH_LIMIT, "name too long!");
+        let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        DirEntry {
+            name: bytes,
+            inode_number,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_SIZE) }
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SIZE) }
+    }
+
+    pub fn name(&self) -> &str {
+        let len = (0usize..).find(|i| self.name[*i] == 0).unwrap();
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
@@ -0,0 +1,22 @@
+// 一个简单的文件系统，磁盘布局依次为：
+// 超级块 | inode 位图 | inode 区域 | 数据块位图 | 数据块区域
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod layout;
+#[cfg(test)]
+mod tests;
+mod vfs;
+
+pub const BLOCK_SIZE: usize = 512;
+
+pub use block_cache::{block_cache_clear, block_cache_sync_all};
+pub use block_dev::{BlockDevice, IoError};
+pub use efs::EasyFileSystem;
+pub use layout::{MAX_FILE_SIZE, NAME_LENGTH_LIMIT};
+pub use vfs::{FsError, Inode};
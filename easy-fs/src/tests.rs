@@ -0,0 +1,259 @@
+// 在宿主机上以内存作为块设备运行的测试
+use super::block_cache::block_cache_clear;
+use super::layout::MAX_FILE_SIZE;
+use super::{
+    block_cache_sync_all, BlockDevice, EasyFileSystem, FsError, Inode, IoError, BLOCK_SIZE,
+    NAME_LENGTH_LIMIT,
+};
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::{Mutex, MutexGuard};
+
+// inode 位图的块数，最多 4096 个文件
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+struct MemDevice {
+    blocks: Mutex<Vec<[u8; BLOCK_SIZE]>>,
+    // 为 true 时所有读写都失败，模拟磁盘故障
+    broken: AtomicBool,
+}
+
+impl BlockDevice for MemDevice {
+    fn block_count(&self) -> usize {
+        self.blocks.lock().len()
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), IoError> {
+        if self.broken.load(Ordering::Relaxed) {
+            return Err(IoError);
+        }
+        buf.copy_from_slice(&self.blocks.lock()[block_id]);
+        Ok(())
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), IoError> {
+        if self.broken.load(Ordering::Relaxed) {
+            return Err(IoError);
+        }
+        self.blocks.lock()[block_id].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+// 块缓存只以块号区分，各个测试不能同时运行
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+// 在 blocks 个块的内存设备上新建文件系统，返回的锁在测试结束前都要持有
+fn setup(blocks: usize) -> (MutexGuard<'static, ()>, Arc<MemDevice>, Arc<Inode>) {
+    let guard = TEST_LOCK.lock();
+    let _ = block_cache_clear();
+    let block_device = Arc::new(MemDevice {
+        blocks: Mutex::new(vec![[0; BLOCK_SIZE]; blocks]),
+        broken: AtomicBool::new(false),
+    });
+    let efs =
+        EasyFileSystem::create(block_device.clone(), blocks as u32, INODE_BITMAP_BLOCKS).unwrap();
+    let root = Arc::new(EasyFileSystem::root_inode(&efs));
+    (guard, block_device, root)
+}
+
+// 丢弃块缓存后重新打开块设备上的文件系统
+fn reopen(block_device: Arc<dyn BlockDevice>) -> Arc<Inode> {
+    block_cache_clear().unwrap();
+    let efs = EasyFileSystem::open(block_device).unwrap();
+    Arc::new(EasyFileSystem::root_inode(&efs))
+}
+
+#[test]
+fn files_and_dirs() {
+    let (_guard, _, root) = setup(4096);
+    // 同一目录下不能有同名文件
+    let filea = root.create("filea").unwrap().unwrap();
+    assert!(root.create("filea").unwrap().is_none());
+    let greet = b"Hello, world!";
+    assert_eq!(filea.write_at(0, greet), Ok(Some(greet.len())));
+    let mut buf = [0u8; 233];
+    let len = filea.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf[..len], greet);
+    // 读取超过文件末尾的部分
+    assert_eq!(filea.read_at(greet.len(), &mut buf), Ok(0));
+
+    // 子目录与路径查找
+    let dir = root.create_dir("bin").unwrap().unwrap();
+    assert_eq!(dir.is_dir(), Ok(true));
+    let fileb = dir.create("fileb").unwrap().unwrap();
+    fileb.write_at(0, b"in a directory").unwrap();
+    let found = root.clone().find_path("/bin/fileb").unwrap().unwrap();
+    assert_eq!(found.read_all().unwrap(), b"in a directory");
+    assert!(root.clone().find_path("bin/filec").unwrap().is_none());
+    // 文件中不能新建文件
+    assert!(filea.create("x").unwrap().is_none());
+    let mut names = root.ls().unwrap();
+    names.sort();
+    assert_eq!(names, ["bin", "filea"]);
+    // 按序号逐个读出目录项，与 ls 的顺序一致
+    let names = root.ls().unwrap();
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(root.dirent_name(i), Ok(Some(name.clone())));
+    }
+    assert_eq!(root.dirent_name(names.len()), Ok(None));
+}
+
+#[test]
+fn indirect_blocks() {
+    let (_guard, _, root) = setup(4096);
+    // 各种长度的文件，依次覆盖直接索引、一级与二级间接索引
+    let file = root.create("random").unwrap().unwrap();
+    for &len in [
+        4 * BLOCK_SIZE,
+        28 * BLOCK_SIZE + 1,
+        (28 + 128) * BLOCK_SIZE,
+        (28 + 128 + 300) * BLOCK_SIZE + 17,
+    ]
+    .iter()
+    {
+        file.clear().unwrap();
+        let data: Vec<u8> = (0..len).map(|i| (i * 31 + i / 7) as u8).collect();
+        // 分块写入，每次写入长度不同
+        let mut offset = 0;
+        let mut step = 1;
+        while offset < len {
+            let end = (offset + step).min(len);
+            assert_eq!(
+                file.write_at(offset, &data[offset..end]),
+                Ok(Some(end - offset))
+            );
+            offset = end;
+            step = step * 3 + 1;
+        }
+        assert_eq!(file.size(), Ok(len));
+        assert_eq!(file.read_all().unwrap(), data);
+    }
+    // 清空后数据块被回收，可以再次写入
+    file.clear().unwrap();
+    assert_eq!(file.size(), Ok(0));
+    assert_eq!(file.write_at(0, b"again"), Ok(Some(5)));
+    assert_eq!(file.read_all().unwrap(), b"again");
+}
+
+#[test]
+fn reopen_image() {
+    let (_guard, block_device, root) = setup(4096);
+    let dir = root.create_dir("bin").unwrap().unwrap();
+    let fileb = dir.create("fileb").unwrap().unwrap();
+    fileb.write_at(0, b"in a directory").unwrap();
+    let data: Vec<u8> = (0..200 * BLOCK_SIZE).map(|i| (i % 251) as u8).collect();
+    let big = root.create("big").unwrap().unwrap();
+    big.write_at(0, &data).unwrap();
+    block_cache_sync_all().unwrap();
+
+    // 之前写入的内容都要从块设备上读出
+    let root = reopen(block_device);
+    let big = root.find("big").unwrap().unwrap();
+    assert_eq!(big.read_all().unwrap(), data);
+    let fileb = root.clone().find_path("bin/fileb").unwrap().unwrap();
+    assert_eq!(fileb.read_all().unwrap(), b"in a directory");
+}
+
+#[test]
+fn max_file_size() {
+    let (_guard, _, root) = setup(16 * 2048);
+    // 最大文件大小之外的部分不写入
+    let big = root.create("big").unwrap().unwrap();
+    assert_eq!(big.write_at(MAX_FILE_SIZE - 1, b"xy"), Ok(Some(1)));
+    assert_eq!(big.write_at(MAX_FILE_SIZE, b"xy"), Ok(Some(0)));
+    assert_eq!(big.size(), Ok(MAX_FILE_SIZE));
+    // 数据块不足时写入失败，文件保持原样，已分配的块被回收
+    let huge = root.create("huge").unwrap().unwrap();
+    let data = vec![0x5au8; MAX_FILE_SIZE];
+    assert_eq!(huge.write_at(0, &data), Ok(None));
+    assert_eq!(huge.size(), Ok(0));
+    big.clear().unwrap();
+    assert_eq!(huge.write_at(0, &data), Ok(Some(MAX_FILE_SIZE)));
+    assert_eq!(huge.read_all().unwrap(), data);
+}
+
+#[test]
+fn no_space() {
+    let (_guard, _, root) = setup(4096);
+    // 逐块写入直到数据块用完
+    let fill = root.create("fill").unwrap().unwrap();
+    let block = [0xa5u8; BLOCK_SIZE];
+    let mut blocks = 0;
+    while fill
+        .write_at(blocks * BLOCK_SIZE, &block)
+        .unwrap()
+        .is_some()
+    {
+        blocks += 1;
+    }
+    assert_eq!(fill.size(), Ok(blocks * BLOCK_SIZE));
+    // 目录需要新的块来存放目录项时，新建文件失败，分配的 inode 被回收
+    let mut files = 0;
+    while root.create(&format!("f{}", files)).unwrap().is_some() {
+        files += 1;
+    }
+    fill.clear().unwrap();
+    let file = root.create(&format!("f{}", files)).unwrap().unwrap();
+    assert_eq!(file.ino(), fill.ino() + files + 1);
+    assert_eq!(root.ls().unwrap().len(), files + 2);
+}
+
+#[test]
+fn no_inode() {
+    let (_guard, _, root) = setup(4096);
+    // 0 号 inode 是根目录
+    let mut files = 1;
+    while root.create(&format!("f{}", files)).unwrap().is_some() {
+        files += 1;
+    }
+    assert_eq!(files, 4096);
+    assert_eq!(root.ls().unwrap().len(), files - 1);
+}
+
+#[test]
+fn io_error() {
+    let (_guard, block_device, root) = setup(4096);
+    let file = root.create("file").unwrap().unwrap();
+    let data: Vec<u8> = (0..100 * BLOCK_SIZE).map(|i| i as u8).collect();
+    file.write_at(0, &data).unwrap();
+    block_cache_clear().unwrap();
+
+    // 磁盘故障时各种操作都返回错误，而不是 panic
+    block_device.broken.store(true, Ordering::Relaxed);
+    let mut buf = [0u8; BLOCK_SIZE];
+    assert_eq!(file.read_at(50 * BLOCK_SIZE, &mut buf), Err(IoError));
+    assert_eq!(file.write_at(data.len(), &buf), Err(IoError));
+    assert!(root.find("file").is_err());
+    assert_eq!(root.create("other").err(), Some(FsError::Io));
+    assert_eq!(file.clear(), Err(IoError));
+
+    // 磁盘恢复之后，文件内容没有被破坏
+    block_device.broken.store(false, Ordering::Relaxed);
+    assert_eq!(file.read_all().unwrap(), data);
+    assert!(root.find("other").unwrap().is_none());
+}
+
+#[test]
+fn long_name() {
+    let (_guard, _, root) = setup(4096);
+    // 恰好 NAME_LENGTH_LIMIT 个字节的名字可以使用
+    let name = "n".repeat(NAME_LENGTH_LIMIT);
+    root.create(&name).unwrap().unwrap();
+    assert!(root.find(&name).unwrap().is_some());
+    // 超过限制的名字被拒绝，而不是截断后存入目录
+    let long = "n".repeat(NAME_LENGTH_LIMIT + 1);
+    assert_eq!(root.create(&long).err(), Some(FsError::NameTooLong));
+    assert_eq!(root.create_dir(&long).err(), Some(FsError::NameTooLong));
+    // 截断会把最后一个多字节字符从中间切开
+    let split = format!("{}é", "n".repeat(NAME_LENGTH_LIMIT - 1));
+    assert_eq!(root.create(&split).err(), Some(FsError::NameTooLong));
+    let utf8 = format!("{}n", "é".repeat(NAME_LENGTH_LIMIT / 2));
+    root.create(&utf8).unwrap().unwrap();
+    let mut names = root.ls().unwrap();
+    names.sort();
+    assert_eq!(names, [name, utf8]);
+}
@@ -0,0 +1,314 @@
+use super::block_cache::{block_cache_sync_all, get_block_cache};
+use super::efs::EasyFileSystem;
+use super::layout::{
+    DirEntry, DiskInode, DiskInodeType, DIRENT_SIZE, MAX_FILE_SIZE, NAME_LENGTH_LIMIT,
+};
+use super::{BlockDevice, IoError, BLOCK_SIZE};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+
+// 新建文件或目录失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    Io,
+    // 文件名超过 NAME_LENGTH_LIMIT 个字节
+    NameTooLong,
+}
+
+impl From<IoError> for FsError {
+    fn from(_: IoError) -> Self {
+        FsError::Io
+    }
+}
+
+// 内存中的 inode，通过 (块号, 块内偏移) 找到磁盘上对应的 DiskInode
+pub struct Inode {
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    pub fn new(
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Inode {
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> Result<V, IoError> {
+        Ok(get_block_cache(self.block_id, self.block_device.clone())?
+            .lock()
+            .read(self.block_offset, f))
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> Result<V, IoError> {
+        Ok(get_block_cache(self.block_id, self.block_device.clone())?
+            .lock()
+            .modify(self.block_offset, f))
+    }
+
+    pub fn is_dir(&self) -> Result<bool, IoError> {
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+
+    pub fn is_file(&self) -> Result<bool, IoError> {
+        self.read_disk_inode(|disk_inode| disk_inode.is_file())
+    }
+
+    // 由 DiskInode 在磁盘上的位置得到的编号，同一文件系统内唯一
+    pub fn ino(&self) -> usize {
+        (self.block_id * BLOCK_SIZE + self.block_offset) / core::mem::size_of::<DiskInode>()
+    }
+
+    pub fn size(&self) -> Result<usize, IoError> {
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    // 在目录中查找 name 对应的 inode 编号
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Result<Option<u32>, IoError> {
+        assert!(disk_inode.is_dir());
+        let file_count = disk_inode.size as usize / DIRENT_SIZE;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            assert_eq!(
+                disk_inode.read_at(DIRENT_SIZE * i, dirent.as_bytes_mut(), &self.block_device)?,
+                DIRENT_SIZE,
+            );
+            if dirent.name() == name {
+                return Ok(Some(dirent.inode_number()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn inode_of(&self, fs: &MutexGuard<EasyFileSystem>, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Inode::new(
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ))
+    }
+
+    // 在目录中查找名为 name 的文件或子目录，当前 inode 不是目录或者没有找到时返回 None
+    pub fn find(&self, name: &str) -> Result<Option<Arc<Inode>>, IoError> {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Ok(None);
+            }
+            Ok(self
+                .find_inode_id(name, disk_inode)?
+                .map(|inode_id| self.inode_of(&fs, inode_id)))
+        })?
+    }
+
+    // 按以 '/' 分隔的路径查找，路径相对于当前目录
+    pub fn find_path(self: Arc<Self>, path: &str) -> Result<Option<Arc<Inode>>, IoError> {
+        let mut inode = self;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            inode = match inode.find(name)? {
+                Some(inode) => inode,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(inode))
+    }
+
+    // 数据块不足时不做任何修改，返回 false
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> Result<bool, IoError> {
+        if new_size < disk_inode.size {
+            return Ok(true);
+        }
+        assert!(new_size as usize <= MAX_FILE_SIZE, "file too large!");
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Ok(Some(block_id)) => v.push(block_id),
+                result => {
+                    // 回收已经分配的块，回收失败时只能放弃这些块
+                    for block_id in v {
+                        let _ = fs.dealloc_data(block_id);
+                    }
+                    return result.map(|_| false);
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, v, &self.block_device)?;
+        Ok(true)
+    }
+
+    fn create_inode(
+        &self,
+        name: &str,
+        type_: DiskInodeType,
+    ) -> Result<Option<Arc<Inode>>, FsError> {
+        if name.len() > NAME_LENGTH_LIMIT {
+            return Err(FsError::NameTooLong);
+        }
+        let mut fs = self.fs.lock();
+        let exist = self.read_disk_inode(|disk_inode| -> Result<bool, IoError> {
+            Ok(!disk_inode.is_dir() || self.find_inode_id(name, disk_inode)?.is_some())
+        })??;
+        // 当前 inode 不是目录，或者同名文件已经存在
+        if exist {
+            return Ok(None);
+        }
+        let new_inode_id = match fs.alloc_inode()? {
+            Some(inode_id) => inode_id,
+            None => return Ok(None),
+        };
+        // 在目录末尾追加一个目录项
+        let appended = self.modify_disk_inode(|dir_inode| -> Result<bool, IoError> {
+            let file_count = dir_inode.size as usize / DIRENT_SIZE;
+            let new_size = (file_count + 1) * DIRENT_SIZE;
+            if !self.increase_size(new_size as u32, dir_inode, &mut fs)? {
+                return Ok(false);
+            }
+            let dirent = DirEntry::new(name, new_inode_id);
+            dir_inode.write_at(
+                file_count * DIRENT_SIZE,
+                dirent.as_bytes(),
+                &self.block_device,
+            )?;
+            Ok(true)
+        })?;
+        match appended {
+            Ok(true) => {}
+            // 目录没有空间容纳新的目录项，或者读写失败
+            result => {
+                let _ = fs.dealloc_inode(new_inode_id);
+                return result.map(|_| None).map_err(FsError::from);
+            }
+        }
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_inode_block_id as usize, self.block_device.clone())?
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(type_);
+            });
+        let inode = self.inode_of(&fs, new_inode_id);
+        block_cache_sync_all()?;
+        Ok(Some(inode))
+    }
+
+    // 在目录中新建一个空文件
+    // 当前 inode 不是目录、同名文件已经存在或者 inode 与数据块不足时返回 None
+    pub fn create(&self, name: &str) -> Result<Option<Arc<Inode>>, FsError> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+
+    // 在目录中新建一个空的子目录
+    pub fn create_dir(&self, name: &str) -> Result<Option<Arc<Inode>>, FsError> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+
+    // 目录中所有文件与子目录的名字
+    pub fn ls(&self) -> Result<Vec<String>, IoError> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = disk_inode.size as usize / DIRENT_SIZE;
+            let mut v: Vec<String> = Vec::new();
+            for i in 0..file_count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(
+                        i * DIRENT_SIZE,
+                        dirent.as_bytes_mut(),
+                        &self.block_device
+                    )?,
+                    DIRENT_SIZE,
+                );
+                v.push(String::from(dirent.name()));
+            }
+            Ok(v)
+        })?
+    }
+
+    // 目录中第 index 项的名字，超出范围时返回 None，只读出这一个目录项
+    pub fn dirent_name(&self, index: usize) -> Result<Option<String>, IoError> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            if index >= disk_inode.size as usize / DIRENT_SIZE {
+                return Ok(None);
+            }
+            let mut dirent = DirEntry::empty();
+            assert_eq!(
+                disk_inode.read_at(
+                    index * DIRENT_SIZE,
+                    dirent.as_bytes_mut(),
+                    &self.block_device
+                )?,
+                DIRENT_SIZE,
+            );
+            Ok(Some(String::from(dirent.name())))
+        })?
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, IoError> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))?
+    }
+
+    // 写入超过文件末尾时自动扩大文件
+    // 超过最大文件大小的部分不写入，数据块不足时返回 None
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> Result<Option<usize>, IoError> {
+        if offset >= MAX_FILE_SIZE {
+            return Ok(Some(0));
+        }
+        let buf = &buf[..buf.len().min(MAX_FILE_SIZE - offset)];
+        let mut fs = self.fs.lock();
+        let size = self.modify_disk_inode(|disk_inode| {
+            if !self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs)? {
+                return Ok(None);
+            }
+            disk_inode
+                .write_at(offset, buf, &self.block_device)
+                .map(Some)
+        })??;
+        block_cache_sync_all()?;
+        Ok(size)
+    }
+
+    // 读出整个文件
+    pub fn read_all(&self) -> Result<Vec<u8>, IoError> {
+        let mut buf = vec![0u8; self.size()?];
+        let size = self.read_at(0, &mut buf)?;
+        buf.truncate(size);
+        Ok(buf)
+    }
+
+    // 清空文件内容并回收数据块
+    pub fn clear(&self) -> Result<(), IoError> {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let size = disk_inode.size;
+            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device)?;
+            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            for data_block in data_blocks_dealloc.into_iter() {
+                fs.dealloc_data(data_block)?;
+            }
+            Ok(())
+        })??;
+        block_cache_sync_all()
+    }
+}
@@ -0,0 +1,205 @@
+use super::Tid;
+use alloc::vec::Vec;
+
+// 利用率的定点表示，1 对应 UTIL_SCALE
+const UTIL_SCALE: u64 = 1 << 32;
+
+#[derive(Clone)]
+struct RtTask {
+    // 以调度 tick 为单位
+    period: u64,
+    budget: u64,
+    // 当前周期剩余的预算
+    remaining: u64,
+    // 当前周期的截止时间，也是下一个周期的开始
+    deadline: u64,
+    // 是否在等待运行
+    queued: bool,
+    misses: usize,
+}
+
+impl RtTask {
+    fn utilization(&self) -> u64 {
+        utilization(self.period, self.budget)
+    }
+
+    // 开始一个新的周期
+    fn release(&mut self, now: u64) {
+        self.deadline = now.saturating_add(self.period);
+        self.remaining = self.budget;
+    }
+}
+
+// 向上取整，保证接纳的线程的实际利用率之和不超过 1
+// budget 不超过 period，结果不超过 UTIL_SCALE，但中间结果可能超出 u64
+// 工具链中的 u128 还没有 div_ceil
+#[allow(clippy::manual_div_ceil)]
+fn utilization(period: u64, budget: u64) -> u64 {
+    let period = period as u128;
+    ((budget as u128 * UTIL_SCALE as u128 + period - 1) / period) as u64
+}
+
+// 最早截止时间优先的实时调度类，位于普通的 Scheduler 之前
+// 每个实时线程声明周期与每周期的预算，只要有可运行的实时线程，普通线程就不会被调度
+// 时间均以自启动以来的调度 tick 数表示，由调用者给出当前时间 now
+#[derive(Default)]
+pub struct EdfClass {
+    tasks: Vec<Option<RtTask>>,
+    // 所有实时线程的利用率之和
+    utilization: u64,
+    // 所有实时线程错过截止时间的总次数
+    misses: usize,
+}
+
+impl EdfClass {
+    pub const fn new() -> Self {
+        EdfClass {
+            tasks: Vec::new(),
+            utilization: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn contains(&self, tid: Tid) -> bool {
+        self.task(tid).is_some()
+    }
+
+    fn task(&self, tid: Tid) -> Option<&RtTask> {
+        self.tasks.get(tid).and_then(|task| task.as_ref())
+    }
+
+    fn task_mut(&mut self, tid: Tid) -> Option<&mut RtTask> {
+        self.tasks.get_mut(tid).and_then(|task| task.as_mut())
+    }
+
+    // 接纳控制：总利用率不超过 1 时将 tid 设为周期为 period、每周期预算为 budget 的实时线程
+    // tid 已经是实时线程时修改其参数，从下一个周期开始生效
+    pub fn admit(&mut self, tid: Tid, period: u64, budget: u64, now: u64) -> bool {
+        if budget == 0 || budget > period {
+            return false;
+        }
+        let old = self.task(tid).map_or(0, |task| task.utilization());
+        let new = utilization(period, budget);
+        if self.utilization - old + new > UTIL_SCALE {
+            return false;
+        }
+        self.utilization = self.utilization - old + new;
+        if tid >= self.tasks.len() {
+            self.tasks.resize_with(tid + 1, Default::default);
+        }
+        match &mut self.tasks[tid] {
+            Some(task) => {
+                task.period = period;
+                task.budget = budget;
+                task.remaining = task.remaining.min(budget);
+            }
+            slot => {
+                let mut task = RtTask {
+                    period,
+                    budget,
+                    remaining: 0,
+                    deadline: 0,
+                    queued: false,
+                    misses: 0,
+                };
+                task.release(now);
+                *slot = Some(task);
+            }
+        }
+        true
+    }
+
+    // 实时线程 tid 可以运行了
+    // 它在睡眠中度过了截止时间，说明上一个周期的工作已经完成，开始新的周期
+    pub fn push(&mut self, tid: Tid, now: u64) {
+        let task = self.task_mut(tid).expect("not a real-time thread!");
+        if now >= task.deadline {
+            task.release(now);
+        }
+        task.queued = true;
+    }
+
+    // 等待运行的线程到了截止时间还没有用完预算，记一次错过截止时间
+    fn update(&mut self, now: u64) {
+        let mut misses = 0;
+        for task in self.tasks.iter_mut().filter_map(|task| task.as_mut()) {
+            if task.queued && now >= task.deadline {
+                task.misses += 1;
+                misses += 1;
+                task.release(now);
+            }
+        }
+        self.misses += misses;
+    }
+
+    // allowed 中截止时间最早的等待运行的线程及其截止时间
+    fn earliest(&self, allowed: impl Fn(Tid) -> bool) -> Option<(Tid, u64)> {
+        self.tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(tid, task)| match task {
+                Some(task) if task.queued && allowed(tid) => Some((tid, task.deadline)),
+                _ => None,
+            })
+            .min_by_key(|&(tid, deadline)| (deadline, tid))
+    }
+
+    // 从 allowed 的线程中选择截止时间最早的运行，allowed 由调用者按 CPU 亲和性给出
+    pub fn pop(&mut self, now: u64, allowed: impl Fn(Tid) -> bool) -> Option<Tid> {
+        self.update(now);
+        let (tid, _) = self.earliest(allowed)?;
+        self.tasks[tid].as_mut().unwrap().queued = false;
+        Some(tid)
+    }
+
+    // 正在运行的 current 是否应当让给截止时间更早的实时线程
+    // current 不是实时线程时，只要有实时线程等待运行就应当让出
+    // 工具链中的 Option 还没有 is_some_and
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn should_preempt(
+        &mut self,
+        current: Tid,
+        now: u64,
+        allowed: impl Fn(Tid) -> bool,
+    ) -> bool {
+        self.update(now);
+        let deadline = self.task(current).map_or(u64::MAX, |task| task.deadline);
+        self.earliest(allowed)
+            .map_or(false, |(_, earliest)| earliest < deadline)
+    }
+
+    // 实时线程 tid 又运行了一个 tick
+    // 预算耗尽时返回下一个周期开始的时间，在此之前它不能再运行
+    pub fn tick(&mut self, tid: Tid, now: u64) -> Option<u64> {
+        let task = self.task_mut(tid)?;
+        if now >= task.deadline {
+            // 截止时间已过，但还在运行
+            task.misses += 1;
+            task.release(now);
+            self.misses += 1;
+        }
+        let task = self.task_mut(tid)?;
+        task.remaining = task.remaining.saturating_sub(1);
+        if task.remaining == 0 {
+            Some(task.deadline)
+        } else {
+            None
+        }
+    }
+
+    // 线程结束，释放其利用率
+    pub fn exit(&mut self, tid: Tid) {
+        if let Some(task) = self.tasks.get_mut(tid).and_then(|task| task.take()) {
+            self.utilization -= task.utilization();
+        }
+    }
+
+    // 线程 tid 错过截止时间的次数，不是实时线程时返回 None
+    pub fn misses(&self, tid: Tid) -> Option<usize> {
+        self.task(tid).map(|task| task.misses)
+    }
+
+    pub fn total_misses(&self) -> usize {
+        self.misses
+    }
+}
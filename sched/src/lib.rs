@@ -0,0 +1,68 @@
+// 与硬件无关的调度算法，内核中的线程池通过 Scheduler 使用它们
+// 实时线程由 EdfClass 单独调度，优先于所有普通线程
+#![no_std]
+
+extern crate alloc;
+
+mod edf;
+mod mlfq;
+mod rr;
+mod stride;
+#[cfg(test)]
+mod tests;
+
+use alloc::boxed::Box;
+
+pub use edf::EdfClass;
+pub use mlfq::MlfqScheduler;
+pub use rr::RRScheduler;
+pub use stride::StrideScheduler;
+
+pub type Tid = usize;
+
+// 线程迁移到另一个 hart 的就绪队列时随之转移的调度状态
+// 各个 hart 使用同一种调度器，各项的含义由调度器自己解释
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SchedInfo {
+    // 迁移前是否在可运行线程中
+    ready: bool,
+    // 当前时间片剩余的 tick 数
+    time: usize,
+    // 多级反馈队列中所在的级别
+    level: usize,
+    // stride 超出调度器当前最小 stride 的部分
+    lag: u32,
+    priority: usize,
+}
+
+// 调度器位于所有 hart 共享的线程池中，因此要求 Send
+pub trait Scheduler: Send {
+    // 如果 tid 不存在，表明将一个新线程加入线程调度
+    // 否则表明一个已有的线程要继续运行
+    fn push(&mut self, tid: Tid);
+    // 从若干可运行线程中选择一个运行
+    fn pop(&mut self) -> Option<Tid>;
+    // 时钟中断中，提醒调度算法 current 又运行了一个 tick
+    // 多个 hart 共享同一个调度器，因此由调用者给出当前 hart 上运行的线程
+    // 返回的 bool 表示调度算法认为 current 是否需要被切换出去
+    fn tick(&mut self, current: Tid) -> bool;
+    // 告诉调度算法一个线程已经结束，若它还在可运行线程中则一并移除
+    fn exit(&mut self, tid: Tid);
+    // 将线程 tid 从可运行线程中移除，返回它此前是否可运行
+    fn remove(&mut self, tid: Tid) -> bool;
+    // 设置线程的优先级，数值越大得到的 CPU 时间越多，不支持优先级的调度算法忽略它
+    fn set_priority(&mut self, _tid: Tid, _priority: usize) {}
+    // 将线程 tid 迁移出去：从调度器中移除并取出它的调度状态，调度器不知道 tid 时返回 None
+    fn take(&mut self, tid: Tid) -> Option<SchedInfo>;
+    // 迁移来的线程恢复 take 取出的状态，迁移前可运行的线程直接回到可运行线程中
+    fn restore(&mut self, tid: Tid, info: SchedInfo);
+    // 所有可运行的线程，不一定按照将被调度的先后顺序
+    fn ready(&self) -> Box<dyn Iterator<Item = Tid> + '_>;
+    // 可运行线程的个数
+    fn len(&self) -> usize {
+        self.ready().count()
+    }
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,155 @@
+use super::{SchedInfo, Scheduler, Tid};
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+// 多级反馈队列的级数，第 i 级的时间片为基本时间片的 2^i 倍
+const MLFQ_LEVELS: usize = 3;
+// 每隔这么多个 tick 将所有线程提升到最高级，避免低级队列中的线程饥饿
+const MLFQ_BOOST_INTERVAL: usize = 100;
+
+#[derive(Clone, Default)]
+struct MlfqInfo {
+    // 是否是调度器已知的线程，线程结束后清除
+    valid: bool,
+    level: usize,
+    // 当前时间片剩余的 tick 数
+    time: usize,
+}
+
+// 多级反馈队列调度：总是从最高级的非空队列中选择线程
+// 用完整个时间片的线程降一级，时间片用完之前睡眠或让出 CPU 的线程升一级
+pub struct MlfqScheduler {
+    threads: Vec<MlfqInfo>,
+    queues: [VecDeque<Tid>; MLFQ_LEVELS],
+    base_time: usize,
+    // 距离上一次提升经过的 tick 数
+    ticks: usize,
+}
+
+impl MlfqScheduler {
+    // 设置最高级队列中线程连续运行的最大 tick 数
+    pub fn new(base_time_slice: usize) -> Self {
+        MlfqScheduler {
+            threads: Vec::new(),
+            queues: Default::default(),
+            base_time: base_time_slice,
+            ticks: 0,
+        }
+    }
+
+    fn time_slice(&self, level: usize) -> usize {
+        self.base_time << level
+    }
+
+    // 所有线程回到最高级，已经在队列中的线程保持原来的先后顺序
+    fn boost(&mut self) {
+        for level in 1..MLFQ_LEVELS {
+            while let Some(tid) = self.queues[level].pop_front() {
+                self.queues[0].push_back(tid);
+            }
+        }
+        let time = self.time_slice(0);
+        for info in self.threads.iter_mut().filter(|info| info.valid) {
+            info.level = 0;
+            info.time = info.time.min(time);
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    // 分为 1. 新线程 2. 时间片耗尽被切换出的线程 3. 睡眠后被唤醒或主动让出的线程 三种情况
+    fn push(&mut self, tid: Tid) {
+        if tid >= self.threads.len() {
+            self.threads.resize_with(tid + 1, Default::default);
+        }
+        let info = &mut self.threads[tid];
+        let level = if !info.valid {
+            0
+        } else if info.time == 0 {
+            (info.level + 1).min(MLFQ_LEVELS - 1)
+        } else {
+            info.level.saturating_sub(1)
+        };
+        *info = MlfqInfo {
+            valid: true,
+            level,
+            time: self.base_time << level,
+        };
+        self.queues[level].push_back(tid);
+    }
+
+    fn pop(&mut self) -> Option<Tid> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn tick(&mut self, current: Tid) -> bool {
+        self.ticks += 1;
+        if self.ticks >= MLFQ_BOOST_INTERVAL {
+            self.ticks = 0;
+            self.boost();
+        }
+        match self.threads.get_mut(current) {
+            Some(info) if info.valid && info.time > 0 => {
+                info.time -= 1;
+                info.time == 0
+            }
+            _ => true,
+        }
+    }
+
+    fn exit(&mut self, tid: Tid) {
+        self.remove(tid);
+        if let Some(info) = self.threads.get_mut(tid) {
+            info.valid = false;
+        }
+    }
+
+    fn remove(&mut self, tid: Tid) -> bool {
+        for queue in self.queues.iter_mut() {
+            if let Some(i) = queue.iter().position(|&t| t == tid) {
+                queue.remove(i);
+                return true;
+            }
+        }
+        false
+    }
+
+    // 转移级别与剩余的时间片，计算线程迁移之后仍留在低级队列中
+    fn take(&mut self, tid: Tid) -> Option<SchedInfo> {
+        let ready = self.remove(tid);
+        let info = self.threads.get_mut(tid).filter(|info| info.valid)?;
+        info.valid = false;
+        Some(SchedInfo {
+            ready,
+            time: info.time,
+            level: info.level,
+            ..SchedInfo::default()
+        })
+    }
+
+    // 可运行的线程直接放入原来级别的队列，不经过 push 的升降级
+    fn restore(&mut self, tid: Tid, info: SchedInfo) {
+        if tid >= self.threads.len() {
+            self.threads.resize_with(tid + 1, Default::default);
+        }
+        let level = info.level.min(MLFQ_LEVELS - 1);
+        self.threads[tid] = MlfqInfo {
+            valid: true,
+            level,
+            time: info.time,
+        };
+        if info.ready {
+            self.queues[level].push_back(tid);
+        }
+    }
+
+    // 从最高级队列开始，按将被调度的先后顺序
+    fn ready(&self) -> Box<dyn Iterator<Item = Tid> + '_> {
+        Box::new(self.queues.iter().flatten().copied())
+    }
+
+    fn len(&self) -> usize {
+        self.queues.iter().map(|queue| queue.len()).sum()
+    }
+}
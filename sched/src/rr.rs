@@ -0,0 +1,133 @@
+use super::{SchedInfo, Scheduler, Tid};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::iter;
+
+#[derive(Default)]
+struct RRInfo {
+    valid: bool,
+    time: usize,
+    prev: usize,
+    next: usize,
+}
+
+pub struct RRScheduler {
+    threads: Vec<RRInfo>,
+    max_time: usize,
+}
+
+impl RRScheduler {
+    // 设置每个线程连续运行的最大 tick 数
+    pub fn new(max_time_slice: usize) -> Self {
+        let mut rr = RRScheduler {
+            threads: Vec::default(),
+            max_time: max_time_slice,
+        };
+        rr.threads.push(RRInfo {
+            valid: false,
+            time: 0,
+            prev: 0,
+            next: 0,
+        });
+        rr
+    }
+}
+
+impl Scheduler for RRScheduler {
+    // 分为 1. 新线程 2. 时间片耗尽被切换出的线程 两种情况
+    fn push(&mut self, tid: Tid) {
+        let tid = tid + 1;
+        if tid + 1 > self.threads.len() {
+            self.threads.resize_with(tid + 1, Default::default);
+        }
+
+        if self.threads[tid].time == 0 {
+            self.threads[tid].time = self.max_time;
+        }
+
+        let prev = self.threads[0].prev;
+        self.threads[tid].valid = true;
+        self.threads[prev].next = tid;
+        self.threads[tid].prev = prev;
+        self.threads[0].prev = tid;
+        self.threads[tid].next = 0;
+    }
+
+    fn pop(&mut self) -> Option<Tid> {
+        let ret = self.threads[0].next;
+        if ret != 0 {
+            self.remove(ret - 1);
+            Some(ret - 1)
+        } else {
+            None
+        }
+    }
+
+    // 当前线程的可用时间片 -= 1
+    fn tick(&mut self, current: Tid) -> bool {
+        let tid = current + 1;
+        if tid < self.threads.len() && self.threads[tid].time > 0 {
+            self.threads[tid].time -= 1;
+            self.threads[tid].time == 0
+        } else {
+            true
+        }
+    }
+
+    // 清空剩余的时间片，tid 被新线程重用时从完整的时间片开始
+    fn exit(&mut self, tid: Tid) {
+        self.remove(tid);
+        if let Some(info) = self.threads.get_mut(tid + 1) {
+            info.time = 0;
+        }
+    }
+
+    // valid 表示线程在链表中，将其摘下
+    fn remove(&mut self, tid: Tid) -> bool {
+        let tid = tid + 1;
+        if tid >= self.threads.len() || !self.threads[tid].valid {
+            return false;
+        }
+        let next = self.threads[tid].next;
+        let prev = self.threads[tid].prev;
+        self.threads[next].prev = prev;
+        self.threads[prev].next = next;
+        self.threads[tid].prev = 0;
+        self.threads[tid].next = 0;
+        self.threads[tid].valid = false;
+        true
+    }
+
+    // 只需转移剩余的时间片
+    fn take(&mut self, tid: Tid) -> Option<SchedInfo> {
+        let ready = self.remove(tid);
+        let info = self.threads.get_mut(tid + 1)?;
+        let time = info.time;
+        info.time = 0;
+        Some(SchedInfo {
+            ready,
+            time,
+            ..SchedInfo::default()
+        })
+    }
+
+    fn restore(&mut self, tid: Tid, info: SchedInfo) {
+        if tid + 2 > self.threads.len() {
+            self.threads.resize_with(tid + 2, Default::default);
+        }
+        self.threads[tid + 1].time = info.time;
+        if info.ready {
+            self.push(tid);
+        }
+    }
+
+    // 从链表头开始，按将被调度的先后顺序
+    fn ready(&self) -> Box<dyn Iterator<Item = Tid> + '_> {
+        let threads = &self.threads;
+        Box::new(
+            iter::successors(Some(threads[0].next), move |&tid| Some(threads[tid].next))
+                .take_while(|&tid| tid != 0)
+                .map(|tid| tid - 1),
+        )
+    }
+}
@@ -0,0 +1,181 @@
+use super::{SchedInfo, Scheduler, Tid};
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+
+// 优先级为 1 的线程每次被调度时 stride 增加的值
+// 所有可运行线程的 stride 之差不超过 BIG_STRIDE，因此可以用回绕的减法比较大小
+const BIG_STRIDE: u32 = i32::MAX as u32;
+// 与 Linux 中 nice 值为 0 的权重相同，内核按权重表将 nice 值换算为优先级
+const DEFAULT_PRIORITY: usize = 1024;
+
+// 堆中的一项，stride 越小越先出堆
+#[derive(PartialEq, Eq)]
+struct StrideEntry {
+    stride: u32,
+    tid: Tid,
+}
+
+impl Ord for StrideEntry {
+    // BinaryHeap 是大根堆，因此 stride 较小者为较大
+    // stride 会回绕，以差值的符号判断先后
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (other.stride.wrapping_sub(self.stride) as i32)
+            .cmp(&0)
+            .then_with(|| other.tid.cmp(&self.tid))
+    }
+}
+
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Default)]
+struct StrideInfo {
+    // 是否是调度器已知的线程，线程结束后清除
+    valid: bool,
+    // 是否在堆中
+    queued: bool,
+    stride: u32,
+    priority: usize,
+    time: usize,
+}
+
+// 步长调度：每次选择 stride 最小的线程，其 stride 增加 BIG_STRIDE / priority
+// 长期来看，各线程得到的时间片个数与优先级成正比
+pub struct StrideScheduler {
+    threads: Vec<StrideInfo>,
+    heap: BinaryHeap<StrideEntry>,
+    // 最近一次出堆的 stride，不大于所有可运行线程的 stride
+    min_stride: u32,
+    max_time: usize,
+}
+
+impl StrideScheduler {
+    // 设置每个线程连续运行的最大 tick 数
+    pub fn new(max_time_slice: usize) -> Self {
+        StrideScheduler {
+            threads: Vec::new(),
+            heap: BinaryHeap::new(),
+            min_stride: 0,
+            max_time: max_time_slice,
+        }
+    }
+
+    // 线程 tid 的调度信息，新线程从当前最小的 stride 开始
+    fn info(&mut self, tid: Tid) -> &mut StrideInfo {
+        if tid >= self.threads.len() {
+            self.threads.resize_with(tid + 1, Default::default);
+        }
+        let min_stride = self.min_stride;
+        let info = &mut self.threads[tid];
+        if !info.valid {
+            *info = StrideInfo {
+                valid: true,
+                queued: false,
+                stride: min_stride,
+                priority: DEFAULT_PRIORITY,
+                time: 0,
+            };
+        }
+        info
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn push(&mut self, tid: Tid) {
+        let min_stride = self.min_stride;
+        let info = self.info(tid);
+        // 睡眠了很久的线程从当前最小的 stride 开始，不会一直占据 CPU
+        // 这样所有可运行线程的 stride 之差才不会超过 BIG_STRIDE
+        if (min_stride.wrapping_sub(info.stride) as i32) > 0 {
+            info.stride = min_stride;
+        }
+        info.queued = true;
+        let stride = info.stride;
+        self.heap.push(StrideEntry { stride, tid });
+    }
+
+    fn pop(&mut self) -> Option<Tid> {
+        let entry = self.heap.pop()?;
+        self.min_stride = entry.stride;
+        let max_time = self.max_time;
+        let info = &mut self.threads[entry.tid];
+        info.queued = false;
+        // 即 pass = BIG_STRIDE / priority
+        info.stride = info.stride.wrapping_add(BIG_STRIDE / info.priority as u32);
+        info.time = max_time;
+        Some(entry.tid)
+    }
+
+    fn tick(&mut self, current: Tid) -> bool {
+        match self.threads.get_mut(current) {
+            Some(info) if info.valid && info.time > 0 => {
+                info.time -= 1;
+                info.time == 0
+            }
+            _ => true,
+        }
+    }
+
+    fn exit(&mut self, tid: Tid) {
+        self.remove(tid);
+        if let Some(info) = self.threads.get_mut(tid) {
+            info.valid = false;
+        }
+    }
+
+    // 堆不支持删除任意元素，只能重建
+    fn remove(&mut self, tid: Tid) -> bool {
+        match self.threads.get_mut(tid) {
+            Some(info) if info.valid && info.queued => info.queued = false,
+            _ => return false,
+        }
+        let heap = mem::take(&mut self.heap);
+        self.heap = heap.into_iter().filter(|entry| entry.tid != tid).collect();
+        true
+    }
+
+    fn set_priority(&mut self, tid: Tid, priority: usize) {
+        self.info(tid).priority = priority.max(1).min(BIG_STRIDE as usize);
+    }
+
+    // 各个调度器的 stride 互不相关，只转移与最小 stride 的差值
+    fn take(&mut self, tid: Tid) -> Option<SchedInfo> {
+        let ready = self.remove(tid);
+        let min_stride = self.min_stride;
+        let info = self.threads.get_mut(tid).filter(|info| info.valid)?;
+        info.valid = false;
+        let lag = info.stride.wrapping_sub(min_stride);
+        Some(SchedInfo {
+            ready,
+            time: info.time,
+            lag: if (lag as i32) > 0 { lag } else { 0 },
+            priority: info.priority,
+            ..SchedInfo::default()
+        })
+    }
+
+    fn restore(&mut self, tid: Tid, sched_info: SchedInfo) {
+        let min_stride = self.min_stride;
+        let info = self.info(tid);
+        info.stride = min_stride.wrapping_add(sched_info.lag);
+        info.priority = sched_info.priority;
+        info.time = sched_info.time;
+        if sched_info.ready {
+            self.push(tid);
+        }
+    }
+
+    fn ready(&self) -> Box<dyn Iterator<Item = Tid> + '_> {
+        Box::new(self.heap.iter().map(|entry| entry.tid))
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
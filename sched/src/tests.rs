@@ -0,0 +1,278 @@
+// 在宿主机上运行的测试
+use super::{EdfClass, MlfqScheduler, RRScheduler, Scheduler, StrideScheduler, Tid};
+use alloc::vec::Vec;
+
+// 每个调度算法都应当满足的性质
+fn conformance(name: &str, scheduler: &mut dyn Scheduler) {
+    assert!(scheduler.is_empty() && scheduler.pop().is_none());
+    for tid in 0..4 {
+        scheduler.push(tid);
+    }
+    assert_eq!(scheduler.len(), 4);
+    let mut ready: Vec<Tid> = scheduler.ready().collect();
+    ready.sort();
+    assert_eq!(ready, [0, 1, 2, 3]);
+
+    // 移除一个可运行线程，它不会再被调度
+    assert!(scheduler.remove(2));
+    assert!(!scheduler.remove(2));
+    assert_eq!(scheduler.len(), 3);
+    assert!(scheduler.ready().all(|tid| tid != 2));
+    let mut popped = [false; 4];
+    while let Some(tid) = scheduler.pop() {
+        assert!(tid != 2 && !popped[tid], "{} scheduler test failed", name);
+        popped[tid] = true;
+    }
+    assert_eq!(popped, [true, true, false, true]);
+    assert!(scheduler.is_empty() && !scheduler.remove(0));
+
+    // 结束的线程即使还在可运行线程中也不会再被调度
+    scheduler.push(1);
+    scheduler.exit(1);
+    assert!(scheduler.is_empty() && scheduler.pop().is_none());
+
+    // 设置优先级不影响线程是否可运行
+    scheduler.push(5);
+    scheduler.set_priority(5, 3);
+    scheduler.set_priority(6, 3);
+    assert_eq!(scheduler.len(), 1);
+    assert_eq!(scheduler.pop(), Some(5));
+
+    // 一直可以运行的线程轮流用完时间片，每个都能得到运行
+    let mut counts = [0usize; 3];
+    for tid in 0..3 {
+        scheduler.push(tid);
+    }
+    for _ in 0..30 {
+        let tid = scheduler.pop().unwrap();
+        counts[tid] += 1;
+        while !scheduler.tick(tid) {}
+        scheduler.push(tid);
+    }
+    assert!(
+        counts.iter().all(|&count| count > 0),
+        "{} scheduler test failed: {:?}",
+        name,
+        counts
+    );
+    for tid in 0..3 {
+        scheduler.exit(tid);
+    }
+    assert!(scheduler.is_empty());
+}
+
+#[test]
+fn rr_conformance() {
+    conformance("rr", &mut RRScheduler::new(2));
+}
+
+#[test]
+fn stride_conformance() {
+    conformance("stride", &mut StrideScheduler::new(2));
+}
+
+#[test]
+fn mlfq_conformance() {
+    conformance("mlfq", &mut MlfqScheduler::new(1));
+}
+
+// 迁移测试：线程的可运行状态随之转移，调度器不知道的线程无法迁移
+fn migration(name: &str, from: &mut dyn Scheduler, to: &mut dyn Scheduler) {
+    assert!(from.take(0).is_none());
+    from.push(0);
+    from.push(1);
+    // 可运行的线程迁移之后直接可运行
+    let info = from.take(0).unwrap();
+    assert_eq!(from.ready().collect::<Vec<Tid>>(), [1], "{}", name);
+    to.restore(0, info);
+    assert_eq!(to.len(), 1);
+    assert_eq!(to.pop(), Some(0));
+    // 正在运行的线程迁移之后要 push 才可运行
+    let info = to.take(0).unwrap();
+    from.restore(0, info);
+    assert_eq!(from.len(), 1, "{}", name);
+    from.push(0);
+    let mut ready: Vec<Tid> = from.ready().collect();
+    ready.sort();
+    assert_eq!(ready, [0, 1], "{}", name);
+}
+
+#[test]
+fn rr_migration() {
+    migration("rr", &mut RRScheduler::new(2), &mut RRScheduler::new(2));
+}
+
+#[test]
+fn stride_migration() {
+    migration(
+        "stride",
+        &mut StrideScheduler::new(2),
+        &mut StrideScheduler::new(2),
+    );
+}
+
+#[test]
+fn mlfq_migration() {
+    migration(
+        "mlfq",
+        &mut MlfqScheduler::new(1),
+        &mut MlfqScheduler::new(1),
+    );
+}
+
+// 步长调度测试：优先级为 1..=5 的线程一直可以运行，它们得到的时间片个数应当与优先级成正比
+#[test]
+fn stride_share() {
+    const ROUNDS: usize = 1500;
+    let mut scheduler = StrideScheduler::new(1);
+    for tid in 0..5 {
+        scheduler.set_priority(tid, tid + 1);
+        scheduler.push(tid);
+    }
+    let mut counts = [0usize; 5];
+    // 运行足够多轮，stride 会多次回绕
+    for _ in 0..ROUNDS {
+        let tid = scheduler.pop().unwrap();
+        counts[tid] += 1;
+        assert!(scheduler.tick(tid));
+        scheduler.push(tid);
+    }
+    // 总优先级为 15，优先级为 p 的线程应得到约 ROUNDS * p / 15 个时间片
+    for (tid, &count) in counts.iter().enumerate() {
+        let expected = ROUNDS * (tid + 1) / 15;
+        assert!(
+            count + 2 >= expected && count <= expected + 2,
+            "stride test failed: {:?}",
+            counts
+        );
+    }
+}
+
+// 多级反馈队列测试：每次只运行一个 tick 就睡眠的交互线程一直留在最高级
+// 用完时间片的计算线程被降级，只有在定期提升之后才能运行
+#[test]
+fn mlfq_boost() {
+    const ROUNDS: usize = 300;
+    let mut scheduler = MlfqScheduler::new(2);
+    // 0 为计算线程，1 为交互线程
+    scheduler.push(0);
+    scheduler.push(1);
+    let mut hog_runs = 0;
+    for _ in 0..ROUNDS {
+        let tid = scheduler.pop().unwrap();
+        if tid == 0 {
+            hog_runs += 1;
+            while !scheduler.tick(tid) {}
+        } else {
+            assert!(!scheduler.tick(tid));
+        }
+        scheduler.push(tid);
+    }
+    // 约 300 个 tick 中至少发生两次提升
+    assert!(
+        (3..=5).contains(&hog_runs),
+        "mlfq test failed: {}",
+        hog_runs
+    );
+}
+
+// 迁移测试：用完时间片的计算线程每次都迁移到另一个 hart，仍然逐级降低，不会回到最高级
+#[test]
+fn mlfq_migrate_hog() {
+    let mut harts = [MlfqScheduler::new(2), MlfqScheduler::new(2)];
+    // 0 为计算线程，1 为交互线程
+    harts[0].push(0);
+    let mut hart = 0;
+    for &slice in [2, 4, 8, 8].iter() {
+        assert_eq!(harts[hart].pop(), Some(0));
+        let mut ticks = 1;
+        while !harts[hart].tick(0) {
+            ticks += 1;
+        }
+        assert_eq!(ticks, slice);
+        // 迁移到另一个 hart，那里有一个刚加入的交互线程
+        let info = harts[hart].take(0).unwrap();
+        hart = 1 - hart;
+        harts[hart].restore(0, info);
+        harts[hart].push(0);
+        harts[hart].push(1);
+        // 交互线程在最高级，先于计算线程运行
+        assert_eq!(harts[hart].pop(), Some(1));
+        harts[hart].exit(1);
+    }
+}
+
+// 迁移测试：优先级随线程迁移，迁移后得到的时间片仍与优先级成正比
+#[test]
+fn stride_migrate_priority() {
+    let mut from = StrideScheduler::new(1);
+    let mut to = StrideScheduler::new(1);
+    from.set_priority(0, 5);
+    from.push(0);
+    to.set_priority(1, 1);
+    to.push(1);
+    let info = from.take(0).unwrap();
+    to.restore(0, info);
+    let mut counts = [0usize; 2];
+    for _ in 0..60 {
+        let tid = to.pop().unwrap();
+        counts[tid] += 1;
+        assert!(to.tick(tid));
+        to.push(tid);
+    }
+    assert_eq!(counts, [50, 10]);
+}
+
+// 实时调度类测试：接纳控制、截止时间最早者优先、预算耗尽与错过截止时间
+#[test]
+fn edf() {
+    let mut edf = EdfClass::new();
+    // 利用率 1/2 + 1/2，第三个线程不能再接纳
+    assert!(edf.admit(0, 10, 5, 0));
+    assert!(edf.admit(1, 4, 2, 0));
+    assert!(!edf.admit(2, 100, 1, 0));
+    assert!(!edf.admit(3, 4, 5, 0));
+
+    // 线程 1 的截止时间 4 早于线程 0 的 10
+    edf.push(0, 0);
+    edf.push(1, 0);
+    assert!(edf.should_preempt(5, 0, |_| true));
+    assert_eq!(edf.pop(0, |_| true), Some(1));
+    assert!(!edf.should_preempt(1, 0, |_| true));
+    // 用完 2 个 tick 的预算后，要等到下一个周期开始
+    assert_eq!(edf.tick(1, 0), None);
+    assert_eq!(edf.tick(1, 1), Some(4));
+    // 不能在当前 hart 上运行的线程不会被选中
+    assert_eq!(edf.pop(2, |tid| tid != 0), None);
+    assert_eq!(edf.pop(2, |_| true), Some(0));
+    assert_eq!(edf.pop(2, |_| true), None);
+
+    // 线程 1 在新的周期被唤醒，截止时间 8 早于线程 0，线程 0 应当让出
+    edf.push(1, 4);
+    assert!(edf.should_preempt(0, 4, |_| true));
+    // 线程 1 一直没有运行，到了截止时间记一次错过
+    edf.push(0, 4);
+    assert_eq!(edf.pop(9, |_| true), Some(0));
+    assert_eq!(edf.misses(1), Some(1));
+    assert_eq!(edf.misses(0), Some(0));
+    assert_eq!(edf.pop(9, |_| true), Some(1));
+    assert_eq!(edf.total_misses(), 1);
+
+    // 结束的线程释放其利用率
+    edf.exit(0);
+    assert!(!edf.contains(0) && edf.misses(0).is_none());
+    assert!(edf.admit(2, 100, 50, 9));
+}
+
+// 周期与预算很大时利用率的计算不能溢出
+#[test]
+fn edf_large_budget() {
+    let mut edf = EdfClass::new();
+    assert!(edf.admit(0, 1 << 40, 1 << 39, 0));
+    assert!(edf.admit(1, u64::MAX, u64::MAX / 2, 0));
+    assert!(!edf.admit(2, u64::MAX, 1 << 33, 0));
+    edf.exit(1);
+    assert!(edf.admit(2, u64::MAX, 1 << 33, 5));
+    edf.push(2, 5);
+    assert_eq!(edf.pop(5, |_| true), Some(2));
+}
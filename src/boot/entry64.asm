@@ -1,6 +1,30 @@
+    # 与 consts.rs 中的 MAX_HARTS 一致
+    .equ MAX_HARTS, 8
+
     .section .text.entry
     .globl _start
 _start:
+    # a0 = hartid, a1 = 设备树的物理地址
+    # 以下不使用 a0, a1，原样作为参数传给 run_main
+    lui     t2, %hi(run_main)
+    addi    t2, t2, %lo(run_main)
+    j       _boot
+
+    # 其他 hart 由启动 hart 通过 SBI hart_start 从这里启动
+    # a0 = hartid, a1 = hart_start 传入的 opaque
+    .globl _start_secondary
+_start_secondary:
+    lui     t2, %hi(run_secondary)
+    addi    t2, t2, %lo(run_secondary)
+
+_boot:
+    # 编号超出范围的 hart 没有启动栈，关闭中断后停在这里
+    li      t0, MAX_HARTS
+    bgeu    a0, t0, _park
+
+    # 内核运行期间 tp 始终保存当前 hart 的编号
+    mv      tp, a0
+
     lui     t0, %hi(boot_page_table_sv39)
     li      t1, 0xffffffffc0000000 - 0x80000000
     sub     t0, t0, t1
@@ -10,17 +34,27 @@ _start:
     csrw    satp, t0
     sfence.vma
 
-    lui sp, %hi(bootstacktop)
+    # 每个 hart 使用各自的启动栈
+    # sp = bootstacktop - hartid * 4096 * 4
+    lui     sp, %hi(bootstacktop)
+    li      t0, 4096 * 4
+    mul     t0, t0, a0
+    sub     sp, sp, t0
+
+    jr      t2
 
-    lui t0, %hi(run_main)
-    addi t0, t0, %lo(run_main)
-    jr t0
+_park:
+    csrw    sie, zero
+1:
+    wfi
+    j       1b
 
     .section .bss.stack
     .align 12
     .global bootstack
 bootstack:
-    .space 4096 * 4
+    # MAX_HARTS 个启动栈
+    .space 4096 * 4 * MAX_HARTS
     .global bootstacktop
 bootstacktop:
 
@@ -29,4 +63,4 @@ bootstacktop:
 boot_page_table_sv39:
     # 0xffffffff_c0000000 -> 0x80000000 (1G)
     .zero 8 * 511
-    .quad (0x80000 << 10) | 0xcf # VRWXAD
\ No newline at end of file
+    .quad (0x80000 << 10) | 0xcf # VRWXAD
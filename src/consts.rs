@@ -1,10 +1,13 @@
-pub const PHYSICAL_MEMORY_END: usize = 0x88000000;
+pub const PHYSICAL_MEMORY_BEGIN: usize = 0x80000000;
 
 pub const KERNEL_BEGIN_PADDR: usize = 0x80200000;
 pub const KERNEL_BEGIN_VADDR: usize = 0xffffffffc0200000;
 
-pub const MAX_PHYSICAL_MEMORY: usize = 0x8000000;
+pub const MAX_PHYSICAL_MEMORY: usize = 0x40000000;
 pub const MAX_PHYSICAL_PAGES: usize = MAX_PHYSICAL_MEMORY >> 12;
+// 内核所能管理的最高物理地址
+// 空出最后一页，避免其线性映射后的虚拟地址回绕到 0
+pub const PHYSICAL_MEMORY_LIMIT: usize = PHYSICAL_MEMORY_BEGIN + MAX_PHYSICAL_MEMORY - PAGE_SIZE;
 
 pub const KERNEL_HEAP_SIZE: usize = 0x800000;
 
@@ -13,3 +16,9 @@ pub const PHYSICAL_MEMORY_OFFSET: usize = 0xffffffff40000000;
 pub const PAGE_SIZE: usize = 4096;
 
 pub const KERNEL_STACK_SIZE: usize = 0x80000;
+
+pub const USER_STACK_SIZE: usize = 0x80000;
+pub const USER_STACK_OFFSET: usize = 0x80000000 - USER_STACK_SIZE;
+
+// 支持的最多 hart 数，与 entry64.asm 中的 MAX_HARTS 一致
+pub const MAX_HARTS: usize = 8;
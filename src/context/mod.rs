@@ -3,6 +3,7 @@ use riscv::register::sstatus;
 use riscv::register::{scause::Scause, sstatus::Sstatus};
 
 #[repr(C)]
+#[derive(Clone)]
 pub struct StackFrame {
     pub reg: [usize; 32], //寄存器
     pub sstatus: Sstatus, //S 态控制状态寄存器。保存全局中断使能标志，以及许多其他的状态
@@ -43,6 +44,42 @@ impl ContextContent {
         content
     }
 
+    // 为一个新用户线程构造栈上的初始状态信息
+    // 其入口点地址为 entry ，其用户栈栈顶地址为 ustack_top ，其页表为 satp
+    fn new_user_thread(entry: usize, ustack_top: usize, satp: usize) -> ContextContent {
+        ContextContent {
+            ra: __trapret as usize,
+            satp,
+            s: [0; 12],
+            sf: {
+                let mut sf: StackFrame = unsafe { zeroed() };
+                sf.reg[2] = ustack_top;
+                sf.sepc = entry;
+                sf.sstatus = sstatus::read();
+                // 通过 __trapret 中的 sret 返回 U 态
+                sf.sstatus.set_spp(sstatus::SPP::User);
+                sf.sstatus.set_spie(true);
+                sf.sstatus.set_sie(false);
+                sf
+            },
+        }
+    }
+
+    // 为 fork 出的子线程构造栈上的初始状态信息
+    // 子线程从父线程陷入内核时的状态 sf 继续执行，但 fork 的返回值为 0
+    fn new_fork(sf: &StackFrame, satp: usize) -> ContextContent {
+        ContextContent {
+            ra: __trapret as usize,
+            satp,
+            s: [0; 12],
+            sf: {
+                let mut sf = sf.clone();
+                sf.reg[10] = 0;
+                sf
+            },
+        }
+    }
+
     unsafe fn push_at(self, stack_top: usize) -> Context {
         let ptr = (stack_top as *mut ContextContent).sub(1);
         *ptr = self;
@@ -67,6 +104,19 @@ impl Context {
     pub unsafe fn new_kernel_thread(entry: usize, kstack_top: usize, satp: usize) -> Context {
         ContextContent::new_kernel_thread(entry, kstack_top, satp).push_at(kstack_top)
     }
+
+    pub unsafe fn new_user_thread(
+        entry: usize,
+        ustack_top: usize,
+        kstack_top: usize,
+        satp: usize,
+    ) -> Context {
+        ContextContent::new_user_thread(entry, ustack_top, satp).push_at(kstack_top)
+    }
+    pub unsafe fn new_fork(sf: &StackFrame, kstack_top: usize, satp: usize) -> Context {
+        ContextContent::new_fork(sf, satp).push_at(kstack_top)
+    }
+
     pub unsafe fn append_initial_arguments(&self, args: [usize; 3]) {
         let contextContent = &mut *(self.content_addr as *mut ContextContent);
         contextContent.sf.reg[10] = args[0];
@@ -0,0 +1,17 @@
+// 内核使用的块设备
+use alloc::sync::Arc;
+use spin::Once;
+
+// 块设备接口与文件系统共用
+pub use easy_fs::{BlockDevice, IoError, BLOCK_SIZE};
+
+static BLOCK_DEVICE: Once<Arc<dyn BlockDevice>> = Once::new();
+
+// 注册内核使用的块设备，只有第一次注册的生效
+pub fn register(device: Arc<dyn BlockDevice>) {
+    BLOCK_DEVICE.call_once(|| device);
+}
+
+pub fn block_device() -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICE.r#try().cloned()
+}
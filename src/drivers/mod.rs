@@ -0,0 +1,18 @@
+// 外部设备驱动，设备的 MMIO 区域已经通过 MemorySet 线性映射到内核地址空间
+pub mod block;
+pub mod plic;
+pub mod uart;
+pub mod virtio;
+
+pub fn init() {
+    // 其他设备的中断都需要通过 PLIC 注册
+    plic::init();
+    uart::init();
+    virtio::init();
+    println!("++++ setup drivers!   ++++");
+}
+
+// 其他 hart 启动时初始化 hart 自身的状态
+pub fn init_hart() {
+    plic::init_hart();
+}
@@ -0,0 +1,135 @@
+// 平台级中断控制器 PLIC (Platform-Level Interrupt Controller)
+// 各设备的中断源经由 PLIC 以 S 态外部中断的形式送往各个 hart
+use crate::consts::MAX_HARTS;
+use crate::dtb;
+use crate::memory::access_pa_via_va;
+use crate::process::hart_id;
+use core::ptr::{read_volatile, write_volatile};
+use riscv::register::sie;
+use spin::{Mutex, Once};
+
+// QEMU virt 平台最多 1024 个中断源，0 号保留表示没有中断
+const MAX_IRQS: usize = 1024;
+
+// 各寄存器相对 PLIC 基址的偏移
+const PRIORITY: usize = 0x0;
+const ENABLE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const THRESHOLD: usize = 0x20_0000;
+const CLAIM_COMPLETE: usize = 0x20_0004;
+const CONTEXT_STRIDE: usize = 0x1000;
+
+// 所有中断源使用相同的优先级，只要求不为 0
+const DEFAULT_PRIORITY: u32 = 1;
+
+struct Plic {
+    // 寄存器的内核虚拟地址
+    base: usize,
+}
+
+impl Plic {
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    // QEMU virt 上每个 hart 有 M 态与 S 态两个 context，hart 的 S 态 context 为 2 * hartid + 1
+    fn context(hartid: usize) -> usize {
+        2 * hartid + 1
+    }
+
+    fn set_priority(&self, irq: usize, priority: u32) {
+        self.write(PRIORITY + irq * 4, priority);
+    }
+
+    // 优先级不超过 threshold 的中断不会被送往该 context
+    fn set_threshold(&self, hartid: usize, threshold: u32) {
+        self.write(
+            THRESHOLD + Self::context(hartid) * CONTEXT_STRIDE,
+            threshold,
+        );
+    }
+
+    fn set_enable(&self, hartid: usize, irq: usize, enable: bool) {
+        let offset = ENABLE + Self::context(hartid) * ENABLE_STRIDE + irq / 32 * 4;
+        let value = self.read(offset);
+        let mask = 1 << (irq % 32);
+        self.write(offset, if enable { value | mask } else { value & !mask });
+    }
+
+    // 领取当前 hart 上优先级最高的待处理中断，没有时返回 0
+    fn claim(&self, hartid: usize) -> usize {
+        self.read(CLAIM_COMPLETE + Self::context(hartid) * CONTEXT_STRIDE) as usize
+    }
+
+    // 通知 PLIC 该中断已处理完毕，之后才会再次送出同一中断源的中断
+    fn complete(&self, hartid: usize, irq: usize) {
+        self.write(
+            CLAIM_COMPLETE + Self::context(hartid) * CONTEXT_STRIDE,
+            irq as u32,
+        );
+    }
+}
+
+static PLIC: Once<Plic> = Once::new();
+
+// 以中断号为下标的中断处理函数
+static HANDLERS: Mutex<[Option<fn()>; MAX_IRQS]> = Mutex::new([None; MAX_IRQS]);
+
+pub fn init() {
+    if let Some(device) = dtb::board().plic {
+        PLIC.call_once(|| Plic {
+            base: access_pa_via_va(device.base),
+        });
+        println!("plic @ {:#x}", device.base);
+        init_hart();
+    }
+}
+
+// 每个 hart 各自接收外部中断
+pub fn init_hart() {
+    if let Some(plic) = PLIC.r#try() {
+        plic.set_threshold(hart_id(), 0);
+        unsafe {
+            sie::set_sext();
+        }
+    }
+}
+
+// 注册中断号 irq 的处理函数，并在所有 hart 上使能该中断源
+// 没有 PLIC 时返回 false，设备只能以轮询的方式使用
+pub fn register(irq: usize, handler: fn()) -> bool {
+    assert!(irq != 0 && irq < MAX_IRQS, "invalid irq {}!", irq);
+    let plic = match PLIC.r#try() {
+        Some(plic) => plic,
+        None => return false,
+    };
+    HANDLERS.lock()[irq] = Some(handler);
+    plic.set_priority(irq, DEFAULT_PRIORITY);
+    for hartid in 0..dtb::board().hart_count.min(MAX_HARTS) {
+        plic.set_enable(hartid, irq, true);
+    }
+    true
+}
+
+// S 态外部中断处理：依次领取并处理所有待处理的中断
+pub fn handle_interrupt() {
+    let plic = PLIC.r#try().expect("plic is not initialized!");
+    let hartid = hart_id();
+    loop {
+        let irq = plic.claim(hartid);
+        if irq == 0 {
+            break;
+        }
+        // 先取出处理函数再调用，处理函数中不持有 HANDLERS 的锁
+        let handler = HANDLERS.lock()[irq];
+        match handler {
+            Some(handler) => handler(),
+            None => println!("unhandled external interrupt {}", irq),
+        }
+        plic.complete(hartid, irq);
+    }
+}
@@ -0,0 +1,157 @@
+// QEMU virt 平台上的 NS16550A 串口
+// 各寄存器按字节排列 (reg-shift = 0)
+use super::plic;
+use crate::dtb;
+use crate::interrupt::{disable_and_store, restore};
+use crate::memory::access_pa_via_va;
+use crate::sync::WaitQueue;
+use core::ptr::{read_volatile, write_volatile};
+use spin::{Mutex, Once};
+
+const RBR: usize = 0;
+const THR: usize = 0;
+const DLL: usize = 0;
+const IER: usize = 1;
+const DLM: usize = 1;
+const FCR: usize = 2;
+const LCR: usize = 3;
+const MCR: usize = 4;
+const LSR: usize = 5;
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const FCR_ENABLE_AND_CLEAR: u8 = 0x07;
+const LCR_8N1: u8 = 0x03;
+const LCR_DLAB: u8 = 1 << 7;
+// DTR | RTS | OUT2，OUT2 将中断信号送往中断控制器
+const MCR_DTR_RTS_OUT2: u8 = 0x0b;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+pub struct Uart {
+    // 寄存器的内核虚拟地址
+    base: usize,
+}
+
+impl Uart {
+    fn read(&self, reg: usize) -> u8 {
+        unsafe { read_volatile((self.base + reg) as *const u8) }
+    }
+
+    fn write(&self, reg: usize, value: u8) {
+        unsafe { write_volatile((self.base + reg) as *mut u8, value) }
+    }
+
+    fn init(&self) {
+        self.write(IER, 0);
+        // 设置波特率除数，QEMU 并不关心具体的值
+        self.write(LCR, LCR_DLAB);
+        self.write(DLL, 0x03);
+        self.write(DLM, 0);
+        self.write(LCR, LCR_8N1);
+        self.write(FCR, FCR_ENABLE_AND_CLEAR);
+        self.write(MCR, MCR_DTR_RTS_OUT2);
+        // 收到数据时触发中断
+        self.write(IER, IER_RX_AVAILABLE);
+    }
+
+    pub fn putchar(&self, ch: u8) {
+        while self.read(LSR) & LSR_THR_EMPTY == 0 {}
+        self.write(THR, ch);
+    }
+
+    // 没有收到数据时返回 None
+    pub fn try_getchar(&self) -> Option<u8> {
+        if self.read(LSR) & LSR_DATA_READY != 0 {
+            Some(self.read(RBR))
+        } else {
+            None
+        }
+    }
+}
+
+static UART: Once<Uart> = Once::new();
+
+// 初始化之前为 None，此时输入输出仍通过 SBI 完成
+pub fn uart() -> Option<&'static Uart> {
+    UART.r#try()
+}
+
+pub fn init() {
+    if let Some(device) = dtb::board().uart {
+        let uart = UART.call_once(|| Uart {
+            base: access_pa_via_va(device.base),
+        });
+        uart.init();
+        plic::register(device.irq, handle_interrupt);
+        println!("uart @ {:#x}, irq {}", device.base, device.irq);
+    }
+}
+
+const BUFFER_SIZE: usize = 256;
+
+// 接收缓冲区，满了之后新收到的字符被丢弃
+// 在中断处理中写入，因此使用固定大小的环形缓冲区，不分配堆内存
+struct Receiver {
+    buffer: [u8; BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl Receiver {
+    fn push(&mut self, ch: u8) {
+        if self.len < BUFFER_SIZE {
+            self.buffer[(self.head + self.len) % BUFFER_SIZE] = ch;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let ch = self.buffer[self.head];
+        self.head = (self.head + 1) % BUFFER_SIZE;
+        self.len -= 1;
+        Some(ch)
+    }
+}
+
+static RECEIVER: Mutex<Receiver> = Mutex::new(Receiver {
+    buffer: [0; BUFFER_SIZE],
+    head: 0,
+    len: 0,
+});
+// 等待输入的线程
+static RX_WAITERS: WaitQueue = WaitQueue::new();
+
+// 串口中断处理：取出 UART 收到的所有字符放入缓冲区，并唤醒等待输入的线程
+pub fn handle_interrupt() {
+    let uart = match uart() {
+        Some(uart) => uart,
+        None => return,
+    };
+    let mut receiver = RECEIVER.lock();
+    while let Some(ch) = uart.try_getchar() {
+        receiver.push(ch);
+    }
+    if receiver.len > 0 {
+        RX_WAITERS.wake_all();
+    }
+}
+
+// 读入一个字符，没有输入时当前线程进入睡眠，直到串口中断将其唤醒
+pub fn getchar() -> u8 {
+    loop {
+        // 缓冲区的锁也会在中断处理中获取，持有期间必须关闭中断
+        let flags = disable_and_store();
+        let mut receiver = RECEIVER.lock();
+        if let Some(ch) = receiver.pop() {
+            drop(receiver);
+            restore(flags);
+            return ch;
+        }
+        // 在释放锁之前标记为睡眠，不会错过释放锁之后到来的唤醒
+        RX_WAITERS.sleep(receiver);
+        restore(flags);
+    }
+}
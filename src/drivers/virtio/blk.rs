@@ -0,0 +1,258 @@
+// virtio-blk 块设备
+// 请求完成时若设备有中断且调用者是一个线程，线程睡眠等待中断将其唤醒
+// 否则 (如启动阶段) 轮询 used 环
+use super::queue::{VirtQueue, DESC_F_NEXT, DESC_F_WRITE};
+use super::VirtioMmio;
+use crate::drivers::block::{self, BlockDevice, IoError, BLOCK_SIZE};
+use crate::drivers::plic;
+use crate::interrupt::{disable_and_store, restore};
+use crate::memory::{access_pa_via_va, alloc_frames, ContiguousFrames};
+use crate::process;
+use crate::sync::WaitQueue;
+use alloc::sync::Arc;
+use spin::{Mutex, Once};
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_S_OK: u8 = 0;
+
+const QUEUE_SIZE: usize = 16;
+// 每个请求占用 3 个描述符：请求头、数据与状态
+// 同时进行的请求数，受限于描述符个数与一页缓冲区
+const MAX_REQUESTS: usize = 4;
+// 每个请求在缓冲区页中所占的大小
+const REQUEST_SIZE: usize = 1024;
+const HEADER_OFFSET: usize = 0;
+const DATA_OFFSET: usize = 16;
+const STATUS_OFFSET: usize = DATA_OFFSET + BLOCK_SIZE;
+
+#[repr(C)]
+struct RequestHeader {
+    type_: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+struct Inner {
+    queue: VirtQueue,
+    // 请求头、数据与状态都放在这里，设备只能访问物理地址
+    buffers: ContiguousFrames,
+    requests: usize,
+    // 以下均以请求编号为下标
+    busy: [bool; MAX_REQUESTS],
+    done: [bool; MAX_REQUESTS],
+}
+
+impl Inner {
+    fn alloc_request(&mut self) -> Option<usize> {
+        let id = (0..self.requests).find(|&id| !self.busy[id])?;
+        self.busy[id] = true;
+        self.done[id] = false;
+        Some(id)
+    }
+
+    // 请求 id 的缓冲区的物理地址
+    fn buffer_pa(&self, id: usize) -> usize {
+        self.buffers.start_address().as_usize() + id * REQUEST_SIZE
+    }
+
+    fn buffer_va(&self, id: usize) -> usize {
+        access_pa_via_va(self.buffer_pa(id))
+    }
+
+    // 处理 used 环中所有已完成的请求，返回是否有请求完成
+    fn poll(&mut self) -> bool {
+        let mut completed = false;
+        while let Some(head) = self.queue.pop_used() {
+            self.done[head / 3] = true;
+            completed = true;
+        }
+        completed
+    }
+}
+
+pub struct VirtioBlk {
+    mmio: VirtioMmio,
+    // 是否已经通过 PLIC 注册了中断
+    interrupt: bool,
+    // 以 512 字节的扇区为单位
+    capacity: usize,
+    inner: Mutex<Inner>,
+    // 等待请求完成或者空闲请求的线程，有请求完成时全部唤醒
+    waiters: WaitQueue,
+}
+
+impl VirtioBlk {
+    fn new(mmio: VirtioMmio, interrupt: bool) -> Option<Self> {
+        if !mmio.begin_init(0) {
+            return None;
+        }
+        let size = QUEUE_SIZE.min(mmio.max_queue_size(0));
+        if size < 3 {
+            return None;
+        }
+        let queue = VirtQueue::new(size);
+        mmio.setup_queue(0, &queue);
+        let buffers = alloc_frames(1, 0).expect("alloc virtio-blk buffer failed!");
+        let capacity = ((mmio.read_config(4) as u64) << 32 | mmio.read_config(0) as u64) as usize;
+        mmio.finish_init();
+        Some(VirtioBlk {
+            mmio,
+            interrupt,
+            capacity,
+            inner: Mutex::new(Inner {
+                queue,
+                buffers,
+                requests: (size / 3).min(MAX_REQUESTS),
+                busy: [false; MAX_REQUESTS],
+                done: [false; MAX_REQUESTS],
+            }),
+            waiters: WaitQueue::new(),
+        })
+    }
+
+    // 发出一个读写请求并等待其完成
+    // 读请求时数据被复制到 buf，写请求时 buf 中的数据被写入设备
+    // 块号超出范围或者设备返回错误状态时返回 Err
+    fn request(&self, write: bool, block_id: usize, buf: *mut u8) -> Result<(), IoError> {
+        if block_id >= self.capacity {
+            return Err(IoError);
+        }
+        // 线程才能睡眠等待，启动阶段只能轮询
+        let sleep = self.interrupt && process::try_current_tid().is_some();
+        // 缓冲区的锁也会在中断处理中获取，持有期间必须关闭中断
+        let (flags, mut inner, id) = loop {
+            let flags = disable_and_store();
+            let mut inner = self.inner.lock();
+            if let Some(id) = inner.alloc_request() {
+                break (flags, inner, id);
+            }
+            // 所有请求都在进行中，等待其中一个完成
+            if sleep {
+                self.waiters.sleep(inner);
+            } else {
+                self.poll(&mut inner);
+                drop(inner);
+            }
+            restore(flags);
+        };
+
+        let pa = inner.buffer_pa(id);
+        let va = inner.buffer_va(id);
+        unsafe {
+            *((va + HEADER_OFFSET) as *mut RequestHeader) = RequestHeader {
+                type_: if write {
+                    VIRTIO_BLK_T_OUT
+                } else {
+                    VIRTIO_BLK_T_IN
+                },
+                reserved: 0,
+                sector: block_id as u64,
+            };
+            if write {
+                core::ptr::copy_nonoverlapping(buf, (va + DATA_OFFSET) as *mut u8, BLOCK_SIZE);
+            }
+            *((va + STATUS_OFFSET) as *mut u8) = 0xff;
+        }
+        let head = id * 3;
+        let data_flags = if write { 0 } else { DESC_F_WRITE };
+        inner
+            .queue
+            .set_desc(head, pa + HEADER_OFFSET, 16, DESC_F_NEXT, head + 1);
+        inner.queue.set_desc(
+            head + 1,
+            pa + DATA_OFFSET,
+            BLOCK_SIZE,
+            data_flags | DESC_F_NEXT,
+            head + 2,
+        );
+        inner
+            .queue
+            .set_desc(head + 2, pa + STATUS_OFFSET, 1, DESC_F_WRITE, 0);
+        inner.queue.submit(head);
+        self.mmio.notify(0);
+
+        while !inner.done[id] {
+            if sleep {
+                // 在释放锁之前标记为睡眠，不会错过中断处理中的唤醒
+                self.waiters.sleep(inner);
+                inner = self.inner.lock();
+            } else {
+                self.poll(&mut inner);
+            }
+        }
+
+        let status = unsafe { *((va + STATUS_OFFSET) as *const u8) };
+        if !write && status == VIRTIO_BLK_S_OK {
+            unsafe {
+                core::ptr::copy_nonoverlapping((va + DATA_OFFSET) as *const u8, buf, BLOCK_SIZE);
+            }
+        }
+        inner.busy[id] = false;
+        drop(inner);
+        restore(flags);
+        // 可能有线程在等待空闲的请求
+        self.waiters.wake_all();
+        if status == VIRTIO_BLK_S_OK {
+            Ok(())
+        } else {
+            Err(IoError)
+        }
+    }
+
+    // 轮询 used 环，唤醒等待的线程，它们各自检查自己的请求是否完成
+    fn poll(&self, inner: &mut Inner) {
+        if inner.poll() {
+            self.waiters.wake_all();
+        }
+    }
+
+    fn handle_interrupt(&self) {
+        self.mmio.ack_interrupt();
+        self.poll(&mut self.inner.lock());
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn block_count(&self) -> usize {
+        self.capacity
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), IoError> {
+        assert_eq!(buf.len(), BLOCK_SIZE);
+        self.request(false, block_id, buf.as_mut_ptr())
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), IoError> {
+        assert_eq!(buf.len(), BLOCK_SIZE);
+        self.request(true, block_id, buf.as_ptr() as *mut u8)
+    }
+}
+
+// 中断处理函数不带参数，只支持一个 virtio-blk 设备
+static VIRTIO_BLK: Once<Arc<VirtioBlk>> = Once::new();
+
+fn handle_interrupt() {
+    if let Some(blk) = VIRTIO_BLK.r#try() {
+        blk.handle_interrupt();
+    }
+}
+
+pub fn init(mmio: VirtioMmio) {
+    if VIRTIO_BLK.r#try().is_some() {
+        println!("only one virtio-blk device is supported");
+        return;
+    }
+    // 中断处理函数在 VIRTIO_BLK 设置之前什么也不做，而此前也不会有请求
+    let interrupt = mmio.irq != 0 && plic::register(mmio.irq, handle_interrupt);
+    let blk = match VirtioBlk::new(mmio, interrupt) {
+        Some(blk) => Arc::new(blk),
+        None => {
+            println!("failed to initialize virtio-blk");
+            return;
+        }
+    };
+    println!("virtio-blk: {} blocks", blk.capacity);
+    VIRTIO_BLK.call_once(|| blk.clone());
+    block::register(blk);
+}
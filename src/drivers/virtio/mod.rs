@@ -0,0 +1,194 @@
+// virtio-mmio 传输层，同时支持 legacy (version 1) 与 modern (version 2) 接口
+pub mod blk;
+mod queue;
+
+use crate::dtb::{self, Device};
+use crate::memory::access_pa_via_va;
+use core::ptr::{read_volatile, write_volatile};
+use queue::VirtQueue;
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG: usize = 0x100;
+
+// "virt" 的小端表示
+const VIRTIO_MAGIC: u32 = 0x7472_6976;
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_FAILED: u32 = 128;
+
+// modern 设备必须协商的特性位
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+// 设备类型
+const DEVICE_BLOCK: u32 = 2;
+
+pub struct VirtioMmio {
+    // 寄存器的内核虚拟地址
+    base: usize,
+    version: u32,
+    irq: usize,
+}
+
+impl VirtioMmio {
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    // 设备相关的配置空间
+    fn read_config(&self, offset: usize) -> u32 {
+        self.read(CONFIG + offset)
+    }
+
+    // 没有设备的 slot 中 DeviceID 为 0
+    fn probe(device: &Device) -> Option<Self> {
+        let mmio = VirtioMmio {
+            base: access_pa_via_va(device.base),
+            version: 0,
+            irq: device.irq,
+        };
+        if mmio.read(MAGIC_VALUE) != VIRTIO_MAGIC || mmio.read(DEVICE_ID) == 0 {
+            return None;
+        }
+        let version = mmio.read(VERSION);
+        if version != 1 && version != 2 {
+            return None;
+        }
+        Some(VirtioMmio { version, ..mmio })
+    }
+
+    fn device_id(&self) -> u32 {
+        self.read(DEVICE_ID)
+    }
+
+    fn device_features(&self) -> u64 {
+        self.write(DEVICE_FEATURES_SEL, 0);
+        let low = self.read(DEVICE_FEATURES) as u64;
+        self.write(DEVICE_FEATURES_SEL, 1);
+        let high = self.read(DEVICE_FEATURES) as u64;
+        (high << 32) | low
+    }
+
+    fn set_driver_features(&self, features: u64) {
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, features as u32);
+        self.write(DRIVER_FEATURES_SEL, 1);
+        self.write(DRIVER_FEATURES, (features >> 32) as u32);
+    }
+
+    // 重置设备并协商特性，只接受 supported 中列出的特性
+    // 协商失败返回 false
+    fn begin_init(&self, supported: u64) -> bool {
+        self.write(STATUS, 0);
+        self.write(STATUS, STATUS_ACKNOWLEDGE);
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        let mut features = self.device_features() & supported;
+        if self.version == 2 {
+            features |= VIRTIO_F_VERSION_1;
+        }
+        self.set_driver_features(features);
+        if self.version == 1 {
+            // legacy 接口没有 FEATURES_OK，需要告诉设备页的大小
+            self.write(GUEST_PAGE_SIZE, queue::QUEUE_ALIGN as u32);
+            return true;
+        }
+        self.write(
+            STATUS,
+            STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK,
+        );
+        if self.read(STATUS) & STATUS_FEATURES_OK == 0 {
+            self.write(STATUS, STATUS_FAILED);
+            return false;
+        }
+        true
+    }
+
+    fn finish_init(&self) {
+        let status = self.read(STATUS);
+        self.write(STATUS, status | STATUS_DRIVER_OK);
+    }
+
+    // 设备支持的最大队列长度，为 0 表示队列不存在
+    fn max_queue_size(&self, index: usize) -> usize {
+        self.write(QUEUE_SEL, index as u32);
+        self.read(QUEUE_NUM_MAX) as usize
+    }
+
+    // 告诉设备队列 index 的位置
+    fn setup_queue(&self, index: usize, queue: &VirtQueue) {
+        self.write(QUEUE_SEL, index as u32);
+        self.write(QUEUE_NUM, queue.size() as u32);
+        if self.version == 1 {
+            self.write(QUEUE_ALIGN, queue::QUEUE_ALIGN as u32);
+            self.write(QUEUE_PFN, (queue.desc_pa() >> 12) as u32);
+        } else {
+            let (desc, avail, used) = (queue.desc_pa(), queue.avail_pa(), queue.used_pa());
+            self.write(QUEUE_DESC_LOW, desc as u32);
+            self.write(QUEUE_DESC_HIGH, (desc >> 32) as u32);
+            self.write(QUEUE_DRIVER_LOW, avail as u32);
+            self.write(QUEUE_DRIVER_HIGH, (avail >> 32) as u32);
+            self.write(QUEUE_DEVICE_LOW, used as u32);
+            self.write(QUEUE_DEVICE_HIGH, (used >> 32) as u32);
+            self.write(QUEUE_READY, 1);
+        }
+    }
+
+    fn notify(&self, index: usize) {
+        self.write(QUEUE_NOTIFY, index as u32);
+    }
+
+    // 应答设备的中断
+    fn ack_interrupt(&self) {
+        let status = self.read(INTERRUPT_STATUS);
+        self.write(INTERRUPT_ACK, status);
+    }
+}
+
+// 探测 QEMU virt 平台上的所有 virtio-mmio slot
+pub fn init() {
+    for device in dtb::board().virtio_devices() {
+        let mmio = match VirtioMmio::probe(device) {
+            Some(mmio) => mmio,
+            None => continue,
+        };
+        println!(
+            "virtio-mmio @ {:#x}: version {}, device id {}",
+            device.base,
+            mmio.version,
+            mmio.device_id()
+        );
+        match mmio.device_id() {
+            DEVICE_BLOCK => blk::init(mmio),
+            id => println!("unsupported virtio device {}", id),
+        }
+    }
+}
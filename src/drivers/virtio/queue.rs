@@ -0,0 +1,141 @@
+// split virtqueue：描述符表、avail 环与 used 环位于物理上连续的物理页中
+use crate::consts::PAGE_SIZE;
+use crate::memory::{access_pa_via_va, alloc_frames, ContiguousFrames};
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+// legacy 接口要求 used 环按页对齐
+pub const QUEUE_ALIGN: usize = PAGE_SIZE;
+
+pub const DESC_F_NEXT: u16 = 1;
+// 设备写入、驱动读取的缓冲区
+pub const DESC_F_WRITE: u16 = 2;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+pub struct VirtQueue {
+    frames: ContiguousFrames,
+    size: usize,
+    // 各部分相对 frames 起始处的偏移
+    avail_offset: usize,
+    used_offset: usize,
+    // 下一个写入 avail 环的位置
+    avail_idx: u16,
+    // 下一个要处理的 used 环位置
+    last_used: u16,
+}
+
+fn align_up(x: usize, align: usize) -> usize {
+    (x + align - 1) / align * align
+}
+
+impl VirtQueue {
+    // size 必须是 2 的幂
+    pub fn new(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two(),
+            "virtqueue size must be a power of 2!"
+        );
+        let avail_offset = 16 * size;
+        let used_offset = align_up(avail_offset + 6 + 2 * size, QUEUE_ALIGN);
+        let pages = align_up(used_offset + 6 + 8 * size, PAGE_SIZE) / PAGE_SIZE;
+        let frames = alloc_frames(pages, 0).expect("alloc virtqueue failed!");
+        // 设备要求队列初始全为 0
+        unsafe {
+            core::ptr::write_bytes(
+                access_pa_via_va(frames.start_address().as_usize()) as *mut u8,
+                0,
+                pages * PAGE_SIZE,
+            );
+        }
+        VirtQueue {
+            frames,
+            size,
+            avail_offset,
+            used_offset,
+            avail_idx: 0,
+            last_used: 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn desc_pa(&self) -> usize {
+        self.frames.start_address().as_usize()
+    }
+
+    pub fn avail_pa(&self) -> usize {
+        self.desc_pa() + self.avail_offset
+    }
+
+    pub fn used_pa(&self) -> usize {
+        self.desc_pa() + self.used_offset
+    }
+
+    fn va(&self, offset: usize) -> usize {
+        access_pa_via_va(self.desc_pa()) + offset
+    }
+
+    // 设置描述符 index，pa 为缓冲区的物理地址
+    pub fn set_desc(&mut self, index: usize, pa: usize, len: usize, flags: u16, next: usize) {
+        assert!(index < self.size);
+        let desc = self.va(index * 16) as *mut Descriptor;
+        unsafe {
+            write_volatile(
+                desc,
+                Descriptor {
+                    addr: pa as u64,
+                    len: len as u32,
+                    flags,
+                    next: next as u16,
+                },
+            );
+        }
+    }
+
+    // 将以 head 开头的描述符链交给设备
+    pub fn submit(&mut self, head: usize) {
+        let slot = self.avail_idx as usize % self.size;
+        unsafe {
+            write_volatile(
+                self.va(self.avail_offset + 4 + 2 * slot) as *mut u16,
+                head as u16,
+            );
+        }
+        // 设备看到新的 idx 之前，描述符与 avail 环必须已经写入
+        fence(Ordering::SeqCst);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        unsafe {
+            write_volatile(self.va(self.avail_offset + 2) as *mut u16, self.avail_idx);
+        }
+        fence(Ordering::SeqCst);
+    }
+
+    // 取出一个设备已经处理完的描述符链，返回其 head
+    pub fn pop_used(&mut self) -> Option<usize> {
+        fence(Ordering::SeqCst);
+        let used_idx = unsafe { read_volatile(self.va(self.used_offset + 2) as *const u16) };
+        if self.last_used == used_idx {
+            return None;
+        }
+        let slot = self.last_used as usize % self.size;
+        let elem =
+            unsafe { read_volatile(self.va(self.used_offset + 4 + 8 * slot) as *const UsedElem) };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some(elem.id as usize)
+    }
+}
@@ -0,0 +1,253 @@
+// 解析 OpenSBI 传入的扁平设备树 (Flattened Device Tree)
+// 只提取内核需要的信息：物理内存、时钟频率以及 QEMU virt 平台上的各个设备
+use crate::memory::access_pa_via_va;
+use spin::Once;
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+const MAX_DEPTH: usize = 16;
+pub const MAX_MEMORY_REGIONS: usize = 4;
+pub const MAX_VIRTIO_DEVICES: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Region {
+    pub base: usize,
+    pub size: usize,
+}
+
+impl Region {
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Device {
+    pub base: usize,
+    pub size: usize,
+    // 中断号，没有中断时为 0
+    pub irq: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct BoardInfo {
+    pub memory: [Region; MAX_MEMORY_REGIONS],
+    pub memory_count: usize,
+    // time 寄存器每秒增加的次数
+    pub timebase_frequency: usize,
+    pub hart_count: usize,
+    pub uart: Option<Device>,
+    pub plic: Option<Device>,
+    pub clint: Option<Device>,
+    pub virtio: [Device; MAX_VIRTIO_DEVICES],
+    pub virtio_count: usize,
+}
+
+impl BoardInfo {
+    pub fn memory_regions(&self) -> &[Region] {
+        &self.memory[..self.memory_count]
+    }
+
+    pub fn virtio_devices(&self) -> &[Device] {
+        &self.virtio[..self.virtio_count]
+    }
+
+    // 需要映射到内核地址空间的 MMIO 设备
+    pub fn mmio_devices(&self) -> impl Iterator<Item = &Device> {
+        self.uart
+            .iter()
+            .chain(self.plic.iter())
+            .chain(self.virtio_devices().iter())
+    }
+
+    // 包含物理地址 pa 的内存区域
+    pub fn memory_region_of(&self, pa: usize) -> Option<Region> {
+        self.memory_regions()
+            .iter()
+            .find(|r| r.base <= pa && pa < r.end())
+            .copied()
+    }
+}
+
+static BOARD: Once<BoardInfo> = Once::new();
+
+// dtb 为设备树的物理地址
+pub fn init(dtb: usize) {
+    let board = BOARD.call_once(|| unsafe { parse(access_pa_via_va(dtb)) });
+    println!("dtb @ {:#x}, {} harts", dtb, board.hart_count);
+    for region in board.memory_regions() {
+        println!("memory [{:#x}, {:#x})", region.base, region.end());
+    }
+    println!("timebase frequency = {}", board.timebase_frequency);
+    println!(
+        "uart {:x?}, plic {:x?}, clint {:x?}",
+        board.uart, board.plic, board.clint
+    );
+    for device in board.virtio_devices() {
+        println!("virtio-mmio {:x?}", device);
+    }
+}
+
+pub fn board() -> &'static BoardInfo {
+    BOARD.r#try().expect("device tree is not parsed!")
+}
+
+fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+// 读取由 cells 个 32 位单元组成的数
+fn read_cells(data: &[u8], offset: usize, cells: usize) -> usize {
+    (0..cells).fold(0, |acc, i| {
+        (acc << 32) | be32(data, offset + i * 4) as usize
+    })
+}
+
+// 以 '\0' 结尾的字符串
+fn c_str(data: &[u8], offset: usize) -> &[u8] {
+    let len = data[offset..].iter().position(|&c| c == 0).unwrap_or(0);
+    &data[offset..offset + len]
+}
+
+// compatible 属性是以 '\0' 分隔的字符串列表
+fn is_compatible(compatible: &[u8], names: &[&str]) -> bool {
+    compatible
+        .split(|&c| c == 0)
+        .any(|s| names.iter().any(|name| name.as_bytes() == s))
+}
+
+#[derive(Clone, Copy)]
+struct Node<'a> {
+    // 本结点为其子结点规定的地址与长度所占单元数
+    address_cells: usize,
+    size_cells: usize,
+    reg: &'a [u8],
+    irq: usize,
+    compatible: &'a [u8],
+    device_type: &'a [u8],
+}
+
+impl Node<'_> {
+    fn new() -> Self {
+        Node {
+            address_cells: 2,
+            size_cells: 1,
+            reg: &[],
+            irq: 0,
+            compatible: &[],
+            device_type: &[],
+        }
+    }
+}
+
+unsafe fn parse(va: usize) -> BoardInfo {
+    let header = core::slice::from_raw_parts(va as *const u8, 40);
+    assert_eq!(be32(header, 0), FDT_MAGIC, "invalid device tree magic!");
+    let total_size = be32(header, 4) as usize;
+    let data = core::slice::from_raw_parts(va as *const u8, total_size);
+    let off_struct = be32(data, 8) as usize;
+    let off_strings = be32(data, 12) as usize;
+    let strings = &data[off_strings..];
+
+    let mut board = BoardInfo::default();
+    let mut stack = [Node::new(); MAX_DEPTH];
+    let mut depth = 0;
+    let mut pos = off_struct;
+    loop {
+        let token = be32(data, pos);
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                // 跳过结点名，按 4 字节对齐
+                let name = c_str(data, pos);
+                pos = (pos + name.len() + 1 + 3) & !3;
+                depth += 1;
+                assert!(depth < MAX_DEPTH, "device tree too deep!");
+                stack[depth] = Node::new();
+            }
+            FDT_END_NODE => {
+                let parent = stack[depth - 1];
+                collect(&mut board, &stack[depth], &parent);
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = be32(data, pos) as usize;
+                let name = c_str(strings, be32(data, pos + 4) as usize);
+                let value = &data[pos + 8..pos + 8 + len];
+                pos = (pos + 8 + len + 3) & !3;
+                let node = &mut stack[depth];
+                match name {
+                    b"#address-cells" => node.address_cells = be32(value, 0) as usize,
+                    b"#size-cells" => node.size_cells = be32(value, 0) as usize,
+                    b"reg" => node.reg = value,
+                    b"interrupts" => node.irq = be32(value, 0) as usize,
+                    b"compatible" => node.compatible = value,
+                    b"device_type" => node.device_type = c_str(value, 0),
+                    b"timebase-frequency" => {
+                        board.timebase_frequency = read_cells(value, 0, len / 4)
+                    }
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            _ => panic!("invalid device tree token {:#x}!", token),
+        }
+    }
+    board
+}
+
+// 一个结点的属性读取完毕，根据其类型记录下来
+fn collect(board: &mut BoardInfo, node: &Node, parent: &Node) {
+    let entry_size = (parent.address_cells + parent.size_cells) * 4;
+    let reg_at = |i: usize| Region {
+        base: read_cells(node.reg, i * entry_size, parent.address_cells),
+        size: read_cells(
+            node.reg,
+            i * entry_size + parent.address_cells * 4,
+            parent.size_cells,
+        ),
+    };
+    let device = || {
+        let region = reg_at(0);
+        Device {
+            base: region.base,
+            size: region.size,
+            irq: node.irq,
+        }
+    };
+
+    if node.device_type == b"memory" {
+        for i in 0..node.reg.len() / entry_size {
+            if board.memory_count < MAX_MEMORY_REGIONS {
+                board.memory[board.memory_count] = reg_at(i);
+                board.memory_count += 1;
+            }
+        }
+    } else if node.device_type == b"cpu" {
+        board.hart_count += 1;
+    } else if node.reg.len() < entry_size {
+        // 以下均为 MMIO 设备，没有 reg 属性的结点不关心
+    } else if is_compatible(node.compatible, &["ns16550a"]) {
+        board.uart = Some(device());
+    } else if is_compatible(node.compatible, &["riscv,plic0", "sifive,plic-1.0.0"]) {
+        board.plic = Some(device());
+    } else if is_compatible(node.compatible, &["riscv,clint0", "sifive,clint0"]) {
+        board.clint = Some(device());
+    } else if is_compatible(node.compatible, &["virtio,mmio"]) {
+        if board.virtio_count < MAX_VIRTIO_DEVICES {
+            board.virtio[board.virtio_count] = device();
+            board.virtio_count += 1;
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// 挂载在 /dev 下的设备文件
+use super::vfs::{FileType, FsError, FsResult, INode, Stat};
+use crate::io;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+// 控制台，即 io::puts 输出、io::getchar 读入的串口
+pub struct Console;
+
+impl INode for Console {
+    // 至少读入一个字符，没有输入时睡眠等待
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> FsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = io::getchar();
+        Ok(1)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> FsResult<usize> {
+        for &b in buf {
+            io::putchar(b as char);
+        }
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> FsResult<Stat> {
+        Ok(Stat::new(0, FileType::CharDevice, 0))
+    }
+}
+
+// 只读的设备目录，内容在新建时确定
+pub struct DevFs {
+    devices: Vec<(&'static str, Arc<dyn INode>)>,
+}
+
+impl DevFs {
+    pub fn new() -> Arc<dyn INode> {
+        let mut devices: Vec<(&'static str, Arc<dyn INode>)> = Vec::new();
+        devices.push(("console", Arc::new(Console) as Arc<dyn INode>));
+        Arc::new(DevFs { devices })
+    }
+}
+
+impl INode for DevFs {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> FsResult<usize> {
+        Err(FsError::IsDir)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::IsDir)
+    }
+
+    fn stat(&self) -> FsResult<Stat> {
+        Ok(Stat::new(0, FileType::Dir, self.devices.len()))
+    }
+
+    fn readdir(&self, index: usize) -> FsResult<Option<String>> {
+        Ok(self.devices.get(index).map(|(name, _)| String::from(*name)))
+    }
+
+    fn find(&self, name: &str) -> FsResult<Arc<dyn INode>> {
+        self.devices
+            .iter()
+            .find(|(dev, _)| *dev == name)
+            .map(|(_, inode)| inode.clone())
+            .ok_or(FsError::NotFound)
+    }
+
+    fn create(&self, _name: &str, _type: FileType) -> FsResult<Arc<dyn INode>> {
+        Err(FsError::NotSupported)
+    }
+}
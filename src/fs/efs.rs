@@ -0,0 +1,110 @@
+// 将 easy-fs 的 Inode 包装为 VFS 的 INode
+use super::vfs::{FileType, FsError, FsResult, INode, Stat};
+use alloc::string::String;
+use alloc::sync::Arc;
+use easy_fs::FsError as EfsError;
+use easy_fs::{Inode, IoError, MAX_FILE_SIZE, NAME_LENGTH_LIMIT};
+
+pub struct EfsInode(Arc<Inode>);
+
+impl EfsInode {
+    pub fn new(inode: Arc<Inode>) -> Arc<dyn INode> {
+        Arc::new(EfsInode(inode))
+    }
+
+    fn is_dir(&self) -> FsResult<bool> {
+        self.0.is_dir().map_err(io_error)
+    }
+}
+
+fn io_error(_: IoError) -> FsError {
+    FsError::Io
+}
+
+fn efs_error(e: EfsError) -> FsError {
+    match e {
+        EfsError::Io => FsError::Io,
+        EfsError::NameTooLong => FsError::NameTooLong,
+    }
+}
+
+impl INode for EfsInode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> FsResult<usize> {
+        if self.is_dir()? {
+            return Err(FsError::IsDir);
+        }
+        self.0.read_at(offset, buf).map_err(io_error)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> FsResult<usize> {
+        if self.is_dir()? {
+            return Err(FsError::IsDir);
+        }
+        if offset >= MAX_FILE_SIZE {
+            return Err(FsError::FileTooLarge);
+        }
+        // 超过最大文件大小的部分不写入
+        let buf = &buf[..buf.len().min(MAX_FILE_SIZE - offset)];
+        self.0
+            .write_at(offset, buf)
+            .map_err(io_error)?
+            .ok_or(FsError::NoSpace)
+    }
+
+    fn stat(&self) -> FsResult<Stat> {
+        let type_ = if self.is_dir()? {
+            FileType::Dir
+        } else {
+            FileType::Regular
+        };
+        let size = self.0.size().map_err(io_error)?;
+        Ok(Stat::new(self.0.ino(), type_, size))
+    }
+
+    fn readdir(&self, index: usize) -> FsResult<Option<String>> {
+        if !self.is_dir()? {
+            return Err(FsError::NotDir);
+        }
+        self.0.dirent_name(index).map_err(io_error)
+    }
+
+    fn find(&self, name: &str) -> FsResult<Arc<dyn INode>> {
+        if !self.is_dir()? {
+            return Err(FsError::NotDir);
+        }
+        // 与 Linux 一致，查找过长的名字时也返回 ENAMETOOLONG
+        if name.len() > NAME_LENGTH_LIMIT {
+            return Err(FsError::NameTooLong);
+        }
+        self.0
+            .find(name)
+            .map_err(io_error)?
+            .map(EfsInode::new)
+            .ok_or(FsError::NotFound)
+    }
+
+    fn create(&self, name: &str, type_: FileType) -> FsResult<Arc<dyn INode>> {
+        match self.find(name) {
+            Ok(_) => return Err(FsError::Exists),
+            Err(FsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        let inode = match type_ {
+            FileType::Regular => self.0.create(name),
+            FileType::Dir => self.0.create_dir(name),
+            _ => return Err(FsError::NotSupported),
+        };
+        // 不存在同名文件时，只有 inode 或数据块不足才会失败
+        inode
+            .map_err(efs_error)?
+            .map(EfsInode::new)
+            .ok_or(FsError::NoSpace)
+    }
+
+    fn truncate(&self) -> FsResult<()> {
+        if self.is_dir()? {
+            return Err(FsError::IsDir);
+        }
+        self.0.clear().map_err(io_error)
+    }
+}
@@ -0,0 +1,173 @@
+// 打开的文件与每个线程的文件描述符表
+use super::dev::Console;
+use super::vfs::{FileType, FsError, FsResult, INode, Stat};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+// open 的 flags，与 Linux 保持一致
+pub const O_RDONLY: usize = 0;
+pub const O_WRONLY: usize = 1;
+pub const O_RDWR: usize = 2;
+pub const O_CREAT: usize = 0o100;
+pub const O_EXCL: usize = 0o200;
+pub const O_TRUNC: usize = 0o1000;
+pub const O_APPEND: usize = 0o2000;
+
+// lseek 的 whence
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+// 一次 open 得到的文件，dup 与 fork 得到的文件描述符共享同一个 File 及其偏移
+pub struct File {
+    inode: Arc<dyn INode>,
+    readable: bool,
+    writable: bool,
+    append: bool,
+    // 读写磁盘时线程可能睡眠，此时不能持有这个锁
+    // 只在读写前取出偏移、读写后更新偏移时加锁，共享偏移的并发读写不保证原子性
+    offset: Mutex<usize>,
+}
+
+impl File {
+    pub fn new(inode: Arc<dyn INode>, readable: bool, writable: bool, append: bool) -> Self {
+        File {
+            inode,
+            readable,
+            writable,
+            append,
+            offset: Mutex::new(0),
+        }
+    }
+
+    // 控制台、管道等没有偏移的概念
+    fn seekable(&self) -> FsResult<bool> {
+        Ok(match self.inode.stat()?.file_type() {
+            FileType::Regular | FileType::Dir => true,
+            _ => false,
+        })
+    }
+
+    pub fn read(&self, buf: &mut [u8]) -> FsResult<usize> {
+        if !self.readable {
+            return Err(FsError::BadFd);
+        }
+        if !self.seekable()? {
+            return self.inode.read_at(0, buf);
+        }
+        let offset = *self.offset.lock();
+        let len = self.inode.read_at(offset, buf)?;
+        *self.offset.lock() = offset + len;
+        Ok(len)
+    }
+
+    pub fn write(&self, buf: &[u8]) -> FsResult<usize> {
+        if !self.writable {
+            return Err(FsError::BadFd);
+        }
+        if !self.seekable()? {
+            return self.inode.write_at(0, buf);
+        }
+        let offset = if self.append {
+            self.inode.stat()?.size as usize
+        } else {
+            *self.offset.lock()
+        };
+        let len = self.inode.write_at(offset, buf)?;
+        *self.offset.lock() = offset + len;
+        Ok(len)
+    }
+
+    // 返回新的偏移
+    pub fn seek(&self, offset: isize, whence: usize) -> FsResult<usize> {
+        if !self.seekable()? {
+            return Err(FsError::NotSeekable);
+        }
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => *self.offset.lock() as isize,
+            SEEK_END => self.inode.stat()?.size as isize,
+            _ => return Err(FsError::InvalidParam),
+        };
+        let new = base.checked_add(offset).ok_or(FsError::InvalidParam)?;
+        if new < 0 {
+            return Err(FsError::InvalidParam);
+        }
+        *self.offset.lock() = new as usize;
+        Ok(new as usize)
+    }
+
+    pub fn stat(&self) -> FsResult<Stat> {
+        self.inode.stat()
+    }
+
+    // 读出目录的下一项，偏移即为目录项的序号
+    pub fn readdir(&self) -> FsResult<Option<String>> {
+        let offset = *self.offset.lock();
+        let name = self.inode.readdir(offset)?;
+        if name.is_some() {
+            *self.offset.lock() = offset + 1;
+        }
+        Ok(name)
+    }
+}
+
+// 每个线程最多同时打开的文件数
+const MAX_FDS: usize = 32;
+
+// 文件描述符表，以文件描述符为下标
+#[derive(Clone)]
+pub struct FdTable {
+    files: Vec<Option<Arc<File>>>,
+}
+
+impl FdTable {
+    pub fn new() -> Self {
+        FdTable { files: Vec::new() }
+    }
+
+    // 0, 1, 2 都指向控制台
+    pub fn with_stdio() -> Self {
+        let console = Arc::new(File::new(Arc::new(Console), true, true, false));
+        let mut table = FdTable::new();
+        for _ in 0..3 {
+            table.files.push(Some(console.clone()));
+        }
+        table
+    }
+
+    pub fn get(&self, fd: usize) -> FsResult<Arc<File>> {
+        self.files
+            .get(fd)
+            .and_then(|file| file.clone())
+            .ok_or(FsError::BadFd)
+    }
+
+    // 使用最小的空闲文件描述符
+    pub fn add(&mut self, file: Arc<File>) -> FsResult<usize> {
+        if let Some(fd) = self.files.iter().position(|file| file.is_none()) {
+            self.files[fd] = Some(file);
+            return Ok(fd);
+        }
+        if self.files.len() >= MAX_FDS {
+            return Err(FsError::TooManyFiles);
+        }
+        self.files.push(Some(file));
+        Ok(self.files.len() - 1)
+    }
+
+    pub fn close(&mut self, fd: usize) -> FsResult<()> {
+        match self.files.get_mut(fd).and_then(|file| file.take()) {
+            Some(_) => Ok(()),
+            None => Err(FsError::BadFd),
+        }
+    }
+
+    // 新的文件描述符与 fd 共享同一个 File
+    pub fn dup(&mut self, fd: usize) -> FsResult<usize> {
+        let file = self.get(fd)?;
+        self.add(file)
+    }
+}
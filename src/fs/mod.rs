@@ -0,0 +1,143 @@
+// 虚拟文件系统：挂载表与按路径打开文件
+// 根目录挂载块设备上的 easy-fs，其镜像由 easy-fs-fuse 打包
+mod dev;
+mod efs;
+pub mod file;
+pub mod pipe;
+pub mod vfs;
+
+use crate::drivers::block::block_device;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::EasyFileSystem;
+use file::{File, O_APPEND, O_CREAT, O_EXCL, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY};
+use spin::Mutex;
+use vfs::{FileType, FsError, FsResult, INode};
+
+// 挂载点的各级路径名及挂载在此处的文件系统的根目录
+static MOUNTS: Mutex<Vec<(Vec<String>, Arc<dyn INode>)>> = Mutex::new(Vec::new());
+
+// 路径中除去空项与 "." 之外的各级名字
+fn components(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+// 将 root 挂载在 path 处，替换掉原先挂载在此处的文件系统
+pub fn mount(path: &str, root: Arc<dyn INode>) {
+    let point: Vec<String> = components(path).map(String::from).collect();
+    let mut mounts = MOUNTS.lock();
+    mounts.retain(|(p, _)| *p != point);
+    mounts.push((point, root));
+}
+
+// 按路径查找 inode，相对路径同样从根目录开始
+// 从路径前缀最长的挂载点开始逐级查找
+pub fn lookup(path: &str) -> FsResult<Arc<dyn INode>> {
+    let names: Vec<&str> = components(path).collect();
+    let (depth, mut inode) = {
+        let mounts = MOUNTS.lock();
+        mounts
+            .iter()
+            .filter(|(point, _)| {
+                point.len() <= names.len() && point.iter().zip(names.iter()).all(|(a, b)| a == b)
+            })
+            .max_by_key(|(point, _)| point.len())
+            .map(|(point, root)| (point.len(), root.clone()))
+            .ok_or(FsError::NotFound)?
+    };
+    for name in &names[depth..] {
+        inode = inode.find(name)?;
+    }
+    Ok(inode)
+}
+
+// 路径所在的目录以及路径的最后一级名字
+fn lookup_parent(path: &str) -> FsResult<(Arc<dyn INode>, &str)> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    if name.is_empty() || name == "." {
+        return Err(FsError::InvalidParam);
+    }
+    Ok((lookup(dir)?, name))
+}
+
+// 按 O_RDONLY 等 flags 打开文件
+pub fn open(path: &str, flags: usize) -> FsResult<Arc<File>> {
+    let (readable, writable) = match flags & 0b11 {
+        O_RDONLY => (true, false),
+        O_WRONLY => (false, true),
+        O_RDWR => (true, true),
+        _ => return Err(FsError::InvalidParam),
+    };
+    let inode = match lookup(path) {
+        Ok(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return Err(FsError::Exists),
+        Ok(inode) => inode,
+        Err(FsError::NotFound) if flags & O_CREAT != 0 => {
+            let (dir, name) = lookup_parent(path)?;
+            dir.create(name, FileType::Regular)?
+        }
+        Err(e) => return Err(e),
+    };
+    if writable && inode.stat()?.file_type() == FileType::Dir {
+        return Err(FsError::IsDir);
+    }
+    if writable && flags & O_TRUNC != 0 {
+        inode.truncate()?;
+    }
+    Ok(Arc::new(File::new(
+        inode,
+        readable,
+        writable,
+        flags & O_APPEND != 0,
+    )))
+}
+
+// 读出 path 处的整个文件
+pub fn read_file(path: &str) -> FsResult<Vec<u8>> {
+    let inode = lookup(path)?;
+    let stat = inode.stat()?;
+    if stat.file_type() != FileType::Regular {
+        return Err(FsError::IsDir);
+    }
+    let mut data = Vec::new();
+    data.resize(stat.size as usize, 0);
+    let len = inode.read_at(0, &mut data)?;
+    data.truncate(len);
+    Ok(data)
+}
+
+// 目录中所有项的名字
+pub fn list(path: &str) -> FsResult<Vec<String>> {
+    let dir = lookup(path)?;
+    let mut names = Vec::new();
+    while let Some(name) = dir.readdir(names.len())? {
+        names.push(name);
+    }
+    Ok(names)
+}
+
+pub fn init() {
+    mount("/dev", dev::DevFs::new());
+    let device = match block_device() {
+        Some(device) => device,
+        None => {
+            println!("no block device, file system is not mounted");
+            return;
+        }
+    };
+    match EasyFileSystem::open(device) {
+        Some(efs) => {
+            mount(
+                "/",
+                efs::EfsInode::new(Arc::new(EasyFileSystem::root_inode(&efs))),
+            );
+            println!("mount easy-fs, / : {:?}", list("/").unwrap());
+        }
+        None => println!("no easy-fs found on the block device"),
+    }
+}
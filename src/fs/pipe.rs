@@ -0,0 +1,163 @@
+// 匿名管道：读写两端共享一个有界的环形缓冲区
+use super::file::File;
+use super::vfs::{FileType, FsError, FsResult, INode, Stat};
+use crate::interrupt::{disable_and_store, restore};
+use crate::sync::Condvar;
+use alloc::sync::Arc;
+use spin::Mutex;
+
+const PIPE_BUFFER_SIZE: usize = 512;
+
+struct PipeBuffer {
+    data: [u8; PIPE_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+    // 尚未关闭的读端与写端个数
+    readers: usize,
+    writers: usize,
+}
+
+impl PipeBuffer {
+    fn pop(&mut self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.len);
+        for b in buf[..len].iter_mut() {
+            *b = self.data[self.head];
+            self.head = (self.head + 1) % PIPE_BUFFER_SIZE;
+        }
+        self.len -= len;
+        len
+    }
+
+    fn push(&mut self, buf: &[u8]) -> usize {
+        let len = buf.len().min(PIPE_BUFFER_SIZE - self.len);
+        for &b in buf[..len].iter() {
+            self.data[(self.head + self.len) % PIPE_BUFFER_SIZE] = b;
+            self.len += 1;
+        }
+        len
+    }
+}
+
+struct Pipe {
+    buffer: Mutex<PipeBuffer>,
+    // 缓冲区非空或者写端全部关闭
+    readable: Condvar,
+    // 缓冲区未满或者读端全部关闭
+    writable: Condvar,
+}
+
+impl Pipe {
+    // 在关闭异步中断的情况下访问缓冲区，这样睡眠前不会在持有锁时被切换出去
+    fn with_buffer<T>(&self, f: impl FnOnce(&mut PipeBuffer) -> T) -> T {
+        let flags = disable_and_store();
+        let ret = f(&mut self.buffer.lock());
+        restore(flags);
+        ret
+    }
+}
+
+pub struct PipeReader(Arc<Pipe>);
+pub struct PipeWriter(Arc<Pipe>);
+
+impl INode for PipeReader {
+    // 缓冲区为空时睡眠，直到有数据写入或者所有写端都已关闭
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> FsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let pipe = &self.0;
+        let flags = disable_and_store();
+        let mut buffer = pipe.buffer.lock();
+        while buffer.len == 0 && buffer.writers > 0 {
+            buffer = pipe.readable.wait(&pipe.buffer, buffer);
+        }
+        let len = buffer.pop(buf);
+        drop(buffer);
+        restore(flags);
+        pipe.writable.notify_all();
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> FsResult<usize> {
+        Err(FsError::BadFd)
+    }
+
+    fn stat(&self) -> FsResult<Stat> {
+        Ok(Stat::new(
+            0,
+            FileType::Fifo,
+            self.0.with_buffer(|buffer| buffer.len),
+        ))
+    }
+}
+
+impl INode for PipeWriter {
+    // 缓冲区已满时睡眠，直到全部写入或者所有读端都已关闭
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> FsResult<usize> {
+        let pipe = &self.0;
+        let flags = disable_and_store();
+        let mut buffer = pipe.buffer.lock();
+        let mut written = 0;
+        while written < buf.len() && buffer.readers > 0 {
+            let len = buffer.push(&buf[written..]);
+            if len > 0 {
+                written += len;
+                pipe.readable.notify_all();
+            } else {
+                buffer = pipe.writable.wait(&pipe.buffer, buffer);
+            }
+        }
+        drop(buffer);
+        restore(flags);
+        if written == 0 && !buf.is_empty() {
+            return Err(FsError::BrokenPipe);
+        }
+        Ok(written)
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> FsResult<usize> {
+        Err(FsError::BadFd)
+    }
+
+    fn stat(&self) -> FsResult<Stat> {
+        Ok(Stat::new(
+            0,
+            FileType::Fifo,
+            self.0.with_buffer(|buffer| buffer.len),
+        ))
+    }
+}
+
+// 最后一个引用读端的 File 被释放时，唤醒等待的写者，使其得知读端已经关闭
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.0.with_buffer(|buffer| buffer.readers -= 1);
+        self.0.writable.notify_all();
+    }
+}
+
+// 所有写端关闭后，读者读完缓冲区中剩余的数据就会读到 EOF
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.0.with_buffer(|buffer| buffer.writers -= 1);
+        self.0.readable.notify_all();
+    }
+}
+
+// 新建一个管道，返回 (读端, 写端)
+pub fn make_pipe() -> (Arc<File>, Arc<File>) {
+    let pipe = Arc::new(Pipe {
+        buffer: Mutex::new(PipeBuffer {
+            data: [0; PIPE_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+            readers: 1,
+            writers: 1,
+        }),
+        readable: Condvar::new(),
+        writable: Condvar::new(),
+    });
+    let reader = File::new(Arc::new(PipeReader(pipe.clone())), true, false, false);
+    let writer = File::new(Arc::new(PipeWriter(pipe)), false, true, false);
+    (Arc::new(reader), Arc::new(writer))
+}
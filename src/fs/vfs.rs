@@ -0,0 +1,107 @@
+// 各种文件系统与设备共同实现的 inode 接口
+use alloc::string::String;
+use alloc::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    // 路径不存在
+    NotFound,
+    // 路径中间的一项不是目录
+    NotDir,
+    // 不能对目录进行读写
+    IsDir,
+    // 要新建的文件已经存在
+    Exists,
+    // 文件描述符无效，或者文件没有以相应的方式打开
+    BadFd,
+    // 打开的文件过多
+    TooManyFiles,
+    // 控制台、管道等不能 seek
+    NotSeekable,
+    // 向所有读端都已关闭的管道写入
+    BrokenPipe,
+    InvalidParam,
+    NoSpace,
+    // 写入位置超过文件系统支持的最大文件大小
+    FileTooLarge,
+    NotSupported,
+    // 块设备读写失败
+    Io,
+    // 文件名超过文件系统支持的长度
+    NameTooLong,
+}
+
+pub type FsResult<T> = Result<T, FsError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Dir,
+    CharDevice,
+    Fifo,
+}
+
+impl FileType {
+    // 与 Linux 中 st_mode 的文件类型位一致
+    pub fn mode(self) -> u32 {
+        match self {
+            FileType::Regular => 0o100000,
+            FileType::Dir => 0o040000,
+            FileType::CharDevice => 0o020000,
+            FileType::Fifo => 0o010000,
+        }
+    }
+}
+
+// fstat 返回给用户的文件信息，用户库中有相同的定义
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Stat {
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub size: u64,
+}
+
+impl Stat {
+    pub fn new(ino: usize, type_: FileType, size: usize) -> Self {
+        Stat {
+            ino: ino as u64,
+            mode: type_.mode(),
+            nlink: 1,
+            size: size as u64,
+        }
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self.mode & 0o170000 {
+            0o040000 => FileType::Dir,
+            0o020000 => FileType::CharDevice,
+            0o010000 => FileType::Fifo,
+            _ => FileType::Regular,
+        }
+    }
+}
+
+pub trait INode: Send + Sync {
+    // 不能 seek 的设备忽略 offset
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> FsResult<usize>;
+    fn write_at(&self, offset: usize, buf: &[u8]) -> FsResult<usize>;
+    fn stat(&self) -> FsResult<Stat>;
+    // 目录中第 index 项的名字，超出范围时返回 None
+    fn readdir(&self, _index: usize) -> FsResult<Option<String>> {
+        Err(FsError::NotDir)
+    }
+    // 在目录中查找名为 name 的一项
+    fn find(&self, _name: &str) -> FsResult<Arc<dyn INode>> {
+        Err(FsError::NotDir)
+    }
+    // 在目录中新建名为 name 的文件或子目录
+    fn create(&self, _name: &str, _type: FileType) -> FsResult<Arc<dyn INode>> {
+        Err(FsError::NotDir)
+    }
+    // 清空文件内容
+    fn truncate(&self) -> FsResult<()> {
+        Err(FsError::NotSupported)
+    }
+}
@@ -1,18 +1,71 @@
 use crate::consts::*;
+use crate::drivers;
+use crate::dtb;
+use crate::fs;
 use crate::interrupt;
 use crate::memory;
 use crate::process;
+use crate::sbi;
 
-pub fn sys_init() {
+// hartid 与 dtb 由 OpenSBI 通过 a0 与 a1 传入
+pub fn sys_init(hartid: usize, dtb: usize) {
     extern "C" {
         fn end();
     }
+    println!("boot on hart {}", hartid);
+    assert!(hartid < MAX_HARTS, "hartid {} is not supported!", hartid);
+    // 探测 SBI 实现支持的扩展，此前的输出使用 legacy 调用
+    sbi::init();
+    // 设备树所在的物理内存随后会被当作空闲物理页分配出去，因此最先解析
+    dtb::init(dtb);
+    let board = dtb::board();
     interrupt::init();
-    interrupt::timer::init();
+    let memory_end = board
+        .memory_region_of(KERNEL_BEGIN_PADDR)
+        .expect("kernel is not in physical memory!")
+        .end()
+        .min(PHYSICAL_MEMORY_LIMIT);
     memory::init(
         ((end as usize - KERNEL_BEGIN_VADDR + KERNEL_BEGIN_PADDR) >> 12) + 1,
-        PHYSICAL_MEMORY_END >> 12,
+        memory_end >> 12,
     );
+    // 设备的 MMIO 区域已经在 kernel_remap 中映射
+    drivers::init();
+    fs::init();
     process::init();
+    // 时钟中断会驱动线程调度，因此必须在 CPU 初始化之后再开启
+    interrupt::timer::init(board.timebase_frequency as u64);
+}
+
+// 启动其他 hart，然后从启动线程切换到调度线程 idle，不再返回
+pub fn sys_run() {
+    extern "C" {
+        fn _start_secondary();
+    }
+    // hart_start 要求给出入口的物理地址
+    let entry = _start_secondary as usize - KERNEL_BEGIN_VADDR + KERNEL_BEGIN_PADDR;
+    let boot_hart = process::hart_id();
+    for hartid in 0..dtb::board().hart_count.min(MAX_HARTS) {
+        if hartid != boot_hart {
+            let ret = sbi::hart_start(hartid, entry, 0);
+            if !ret.is_ok() {
+                println!("failed to start hart {}, error = {}", hartid, ret.error);
+            }
+        }
+    }
+    process::run();
+}
+
+// 其他 hart 的入口，由 entry64.asm 中的 _start_secondary 跳转而来
+// 全局的初始化已经由启动 hart 完成，这里只初始化 hart 自身的状态
+#[no_mangle]
+pub extern "C" fn run_secondary(hartid: usize) -> ! {
+    println!("hart {} started", hartid);
+    memory::init_hart();
+    interrupt::init();
+    drivers::init_hart();
+    process::init_hart();
+    interrupt::timer::init(dtb::board().timebase_frequency as u64);
     process::run();
+    loop {}
 }
@@ -2,6 +2,7 @@ pub mod timer;
 
 use riscv::register::{
     scause::{Exception, Interrupt, Trap}, //它会记录中断发生的原因，还会记录该中断是不是一个外部中断
+    sie,
     // sepc,                                       它会记录触发中断的那条指令的地址
     sscratch, //根据 sscratch 的值是否为 0 来判断是在 S 态产生的中断还是 U 态（用户态）产生的中断
     sstatus,
@@ -9,7 +10,12 @@ use riscv::register::{
 };
 
 use crate::context::StackFrame;
-use timer::{clock_set_next_event, TICKS};
+use crate::drivers;
+use crate::process;
+use crate::sbi;
+use crate::syscall::syscall;
+use core::sync::atomic::Ordering;
+use timer::TICKS;
 global_asm!(include_str!("trap.asm"));
 
 pub fn init() {
@@ -20,18 +26,33 @@ pub fn init() {
         }
         sscratch::write(0);
         stvec::write(__alltraps as usize, stvec::TrapMode::Direct);
+        // 其他 hart 通过 IPI 唤醒空闲的本 hart
+        sie::set_ssoft();
         sstatus::set_sie();
+        // 允许内核在系统调用中访问用户态内存
+        sstatus::set_sum();
     }
     println!("------------ init interrupt! -------------");
 }
 
+// 用户程序访问非法地址时的退出码，与 shell 中被 SIGSEGV 杀死的进程一致
+const SEGFAULT_EXIT_CODE: usize = 128 + 11;
+
 fn page_fault(tf: &mut StackFrame) {
+    // 延迟分配的页第一次被访问，分配后重新执行触发异常的指令
+    if process::handle_page_fault(tf.stval) {
+        return;
+    }
     println!(
         "{:?} va = {:#x} instruction = {:#x}",
         tf.scause.cause(),
         tf.stval,
         tf.sepc
     );
+    // 用户程序的错误只结束出错的线程
+    if let sstatus::SPP::User = tf.sstatus.spp() {
+        process::exit(SEGFAULT_EXIT_CODE);
+    }
     panic!("page fault!");
 }
 
@@ -39,7 +60,11 @@ fn page_fault(tf: &mut StackFrame) {
 fn trap_handler(sf: &mut StackFrame) {
     match sf.scause.cause() {
         Trap::Exception(Exception::Breakpoint) => breakpoint(&mut sf.sepc),
+        Trap::Exception(Exception::UserEnvCall) => syscall(sf),
         Trap::Interrupt(Interrupt::SupervisorTimer) => timer_handler(),
+        // IPI 只用于唤醒在 wfi 中等待的 hart，清除即可
+        Trap::Interrupt(Interrupt::SupervisorSoft) => sbi::clear_ipi(),
+        Trap::Interrupt(Interrupt::SupervisorExternal) => drivers::plic::handle_interrupt(),
         Trap::Exception(Exception::InstructionPageFault) => page_fault(sf),
         Trap::Exception(Exception::LoadPageFault) => page_fault(sf),
         Trap::Exception(Exception::StorePageFault) => page_fault(sf),
@@ -54,14 +79,16 @@ fn breakpoint(sepc: &mut usize) {
 
 // s态时钟中断处理
 fn timer_handler() {
-    clock_set_next_event();
-    unsafe {
-        TICKS += 1;
-        if TICKS == 100 {
-            TICKS = 0;
-            println!("* 100 ticks *");
-        }
+    // 唤醒到期的线程，并设置下一次时钟中断
+    if !timer::handle_interrupt() {
+        return;
+    }
+    if TICKS.load(Ordering::Relaxed) % 100 == 0 {
+        println!("* 100 ticks *");
     }
+    // 通知 CPU 当前线程又运行了一个 tick
+    // 若时间片耗尽，会在这里切换到 idle 线程
+    process::tick();
 }
 
 #[inline(always)]
@@ -70,7 +97,7 @@ pub fn disable_and_store() -> usize {
     unsafe {
         // clear sstatus 的 SIE 标志位禁用异步中断
         // 返回 clear 之前的 sstatus 状态
-        llvm_asm!("csrci sstatus, 1 << 1" : "=r"(sstatus) ::: "volatile");
+        llvm_asm!("csrrci $0, sstatus, 1 << 1" : "=r"(sstatus) ::: "volatile");
     }
     sstatus
 }
@@ -78,16 +105,17 @@ pub fn disable_and_store() -> usize {
 #[inline(always)]
 pub fn restore(flags: usize) {
     unsafe {
-        // 将 sstatus 设置为 flags 的值
-        llvm_asm!("csrs sstatus, $0" :: "r"(flags) :: "volatile");
+        // 按 flags 恢复 sstatus 的 SIE 标志位
+        llvm_asm!("csrs sstatus, $0" :: "r"(flags & (1 << 1)) :: "volatile");
     }
 }
 
 #[inline(always)]
 pub fn enable_and_wfi() {
     unsafe {
-        // set sstatus 的 SIE 标志位启用异步中断
-        // 并通过 wfi 指令等待下一次异步中断的到来
-        llvm_asm!("csrsi sstatus, 1 << 1; wfi" :::: "volatile");
+        // 通过 wfi 指令等待下一次异步中断的到来
+        // 即使 SIE 为 0，sie 中使能的中断到来时 wfi 也会返回
+        // 因此先 wfi 再 set sstatus 的 SIE 标志位，不会错过两条指令之间到来的中断
+        llvm_asm!("wfi; csrsi sstatus, 1 << 1" :::: "volatile");
     }
 }
@@ -1,29 +1,180 @@
+use crate::consts::MAX_HARTS;
+use crate::interrupt::{disable_and_store, restore};
+use crate::process::{self, hart_id, Tid};
 use crate::sbi::set_timer;
+use alloc::collections::BinaryHeap;
+use core::cmp::Reverse;
+use core::mem;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
 use riscv::register::{sie, time};
+use spin::{Mutex, Once};
 
-pub static mut TICKS: usize = 0;
-static TIMEBASE: u64 = 100000;
+// 已经发生的调度 tick 总数
+pub static TICKS: AtomicUsize = AtomicUsize::new(0);
+// 每秒触发调度 tick 的次数
+const TICKS_PER_SEC: u64 = 100;
+// time 寄存器的计数频率，由设备树给出，默认为 QEMU virt 平台的 10MHz
+static CLOCK_FREQ: AtomicU64 = AtomicU64::new(10000000);
 
-fn get_cycle() -> u64 {
+const NSEC_PER_SEC: u128 = 1_000_000_000;
+
+pub fn clock_freq() -> u64 {
+    CLOCK_FREQ.load(Ordering::Relaxed)
+}
+
+pub fn get_cycle() -> u64 {
     time::read() as u64
 }
 
+// 自启动以来的单调时间，单位为纳秒
+pub fn now_ns() -> u64 {
+    (get_cycle() as u128 * NSEC_PER_SEC / clock_freq() as u128) as u64
+}
+
+pub fn duration_to_cycles(duration: Duration) -> u64 {
+    (duration.as_nanos() * clock_freq() as u128 / NSEC_PER_SEC) as u64
+}
+
+// 两次调度 tick 之间 time 寄存器增加的值
+fn timebase() -> u64 {
+    clock_freq() / TICKS_PER_SEC
+}
+
+// 自启动以来经过的调度 tick 个数，与 hart 个数及是否空闲无关
+pub fn now_ticks() -> u64 {
+    get_cycle() / timebase()
+}
+
+// 每个 hart 下一次调度 tick 的时间，只在运行线程时有意义
+static NEXT_TICK: [AtomicU64; MAX_HARTS] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+// 睡眠中的线程及其到期时间，以小根堆组织，堆顶最早到期
+// 在时钟中断中取出，因此加锁前必须关闭异步中断
+type Timers = BinaryHeap<Reverse<(u64, Tid)>>;
+static TIMERS: Once<Mutex<Timers>> = Once::new();
+
+fn timers() -> &'static Mutex<Timers> {
+    TIMERS.call_once(|| Mutex::new(BinaryHeap::new()))
+}
+
+// 删除满足 f 的定时器，堆不支持删除任意元素，只能重建
+fn remove_timers(timers: &mut Timers, f: impl Fn(u64, Tid) -> bool) {
+    if timers.iter().any(|&Reverse((deadline, tid))| f(deadline, tid)) {
+        let heap = mem::take(timers);
+        *timers = heap
+            .into_iter()
+            .filter(|&Reverse((deadline, tid))| !f(deadline, tid))
+            .collect();
+    }
+}
+
+// 设置下一次时钟中断：最早的定时器到期时间
+// 当前 hart 正在运行线程时还要考虑下一次调度 tick，空闲时则可以一直睡眠到定时器到期
 pub fn clock_set_next_event() {
-    set_timer(get_cycle() + TIMEBASE);
+    let flags = disable_and_store();
+    let mut next = timers()
+        .lock()
+        .peek()
+        .map_or(u64::max_value(), |&Reverse((deadline, _))| deadline);
+    if process::try_current_tid().is_some() {
+        next = next.min(NEXT_TICK[hart_id()].load(Ordering::Relaxed));
+    }
+    set_timer(next);
+    restore(flags);
+}
+
+// 当前 hart 开始运行一个线程，一个时间片之后触发调度 tick
+pub fn start_tick() {
+    NEXT_TICK[hart_id()].store(get_cycle() + timebase(), Ordering::Relaxed);
+    clock_set_next_event();
+}
+
+// 时钟中断处理：唤醒到期的线程，返回是否到达调度 tick
+pub fn handle_interrupt() -> bool {
+    let now = get_cycle();
+    {
+        let mut timers = timers().lock();
+        while let Some(&Reverse((deadline, tid))) = timers.peek() {
+            if deadline > now {
+                break;
+            }
+            timers.pop();
+            process::wakeup(tid);
+        }
+    }
+    let next_tick = &NEXT_TICK[hart_id()];
+    let tick = process::try_current_tid().is_some() && now >= next_tick.load(Ordering::Relaxed);
+    if tick {
+        next_tick.store(now + timebase(), Ordering::Relaxed);
+        TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+    clock_set_next_event();
+    tick
 }
 
-pub fn init() {
+// 当前线程睡眠 duration 的时间
+pub fn sleep(duration: Duration) {
+    let deadline = get_cycle() + duration_to_cycles(duration);
+    let tid = process::current_tid();
+    // 只加入一次定时器，被提前唤醒后继续睡眠时不再重复加入
+    let flags = disable_and_store();
+    timers().lock().push(Reverse((deadline, tid)));
+    restore(flags);
+    loop {
+        let flags = disable_and_store();
+        let mut timers = timers().lock();
+        if get_cycle() >= deadline {
+            // 时钟中断可能还没有取出这个定时器，不能留到之后去唤醒别的睡眠
+            remove_timers(&mut timers, |d, t| d == deadline && t == tid);
+            drop(timers);
+            restore(flags);
+            break;
+        }
+        // 在释放锁之前标记为睡眠，不会错过时钟中断中的唤醒
+        // 切换到 idle 后，idle 会将时钟中断设置在这个到期时间之前
+        process::sleep(timers);
+        restore(flags);
+    }
+}
+
+// 线程 tid 退出，删除它所有的定时器，以免之后唤醒重用了这个 tid 的线程
+// 与时钟中断一样先获取 TIMERS，因此不能在持有线程池的锁时调用
+pub fn cancel(tid: Tid) {
+    let flags = disable_and_store();
+    remove_timers(&mut timers().lock(), |_, t| t == tid);
+    restore(flags);
+}
+
+// 在第 tick 个调度 tick 时唤醒线程 tid，调用者负责将其标记为睡眠
+// 不能在持有线程池的锁时调用，时钟中断中先获取 TIMERS 再唤醒线程
+pub fn wakeup_at(tick: u64, tid: Tid) {
+    let flags = disable_and_store();
+    timers().lock().push(Reverse((tick.saturating_mul(timebase()), tid)));
+    restore(flags);
+}
+
+// freq 为设备树给出的 timebase-frequency，为 0 时沿用默认值
+pub fn init(freq: u64) {
+    if freq != 0 {
+        CLOCK_FREQ.store(freq, Ordering::Relaxed);
+    }
     unsafe {
-        // 初始化时钟中断触发次数
-        TICKS = 0;
         // 设置 sie 的 TI 使能 STIE 位
         sie::set_stimer();
     }
     // 硬件机制问题我们不能直接设置时钟中断触发间隔
-    // 只能当每一次时钟中断触发时
-    // 设置下一次时钟中断的触发时间
-    // 设置为当前时间加上 TIMEBASE
-    // 这次调用用来预处理
+    // 只能当每一次时钟中断触发时，设置下一次时钟中断的触发时间
+    // 这次调用用来预处理，此时尚未运行线程，只有定时器到期才会触发
     clock_set_next_event();
     println!("--------- init timer -------------");
 }
@@ -51,6 +51,11 @@ trap_from_user:
 	csrr s3, stval
 	csrr s4, scause
 
+	# 从用户态陷入时，取回 RESTORE_ALL 中暂存的内核 tp (hartid)
+	andi s5, s1, 1 << 8
+	bnez s5, 1f
+	LOAD tp, 35
+1:
 	STORE s0, 2
 	STORE s1, 32
 	STORE s2, 33
@@ -66,12 +71,16 @@ trap_from_user:
 _to_user:
 	addi s0, sp, 36*XLENB
 	csrw sscratch, s0
+	# 将内核 tp (hartid) 暂存在 scause 的位置，下次从用户态陷入时取回
+	STORE tp, 35
+	# 只有返回用户态时才恢复 tp
+	# 内核线程可能被调度到其他 hart 上，其 tp 必须保持为当前 hartid
+	LOAD x4, 4
 _to_kernel:
     csrw sstatus, s1
     csrw sepc, s2
 	LOAD x1, 1
 	LOAD x3, 3
-    LOAD x4, 4
     LOAD x5, 5
     LOAD x6, 6
     LOAD x7, 7
@@ -1,8 +1,31 @@
+use crate::drivers::uart;
+use crate::interrupt::{disable_and_store, restore};
+use crate::process;
 use crate::sbi;
 use core::fmt::{self, Write};
+use spin::Mutex;
 
+// 串口驱动初始化之前，通过 SBI 输出
 pub fn putchar(ch: char) {
-    sbi::console_putchar(ch as u8 as usize);
+    match uart::uart() {
+        Some(uart) => uart.putchar(ch as u8),
+        None => sbi::console_putchar(ch as u8 as usize),
+    }
+}
+
+// 读入一个字符，没有输入时阻塞
+// 串口驱动初始化之前，通过轮询 SBI 读入
+pub fn getchar() -> u8 {
+    if uart::uart().is_some() {
+        return uart::getchar();
+    }
+    loop {
+        let ch = sbi::console_getchar();
+        if ch != usize::max_value() {
+            return ch as u8;
+        }
+        process::yield_now();
+    }
 }
 
 pub fn puts(s: &str) {
@@ -19,8 +42,14 @@ impl fmt::Write for Stdout {
     }
 }
 
+// 多个 hart 同时输出时，保证每次 print 的内容不会交错
+static STDOUT: Mutex<Stdout> = Mutex::new(Stdout);
+
 pub fn _print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    // 持有锁期间不能被时钟中断打断，否则中断处理中的输出会造成死锁
+    let flags = disable_and_store();
+    STDOUT.lock().write_fmt(args).unwrap();
+    restore(flags);
 }
 
 #[macro_export]
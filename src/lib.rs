@@ -14,9 +14,14 @@ pub mod memory;
 mod context;
 
 mod consts;
+pub mod drivers;
+mod dtb;
+pub mod fs;
 mod interrupt;
 mod lang_items;
 mod process;
 mod sbi;
+pub mod sync;
+mod syscall;
 
 extern crate alloc;
@@ -6,9 +6,23 @@
 #[allow(unused_imports)]
 #[macro_use]
 extern crate os;
+extern crate alloc;
 
-use os::init::sys_init;
-use os::memory::{alloc_frame, dealloc_frame};
+use alloc::vec::Vec;
+use os::drivers::block::{block_device, BLOCK_SIZE};
+use os::fs::{
+    self,
+    file::{FdTable, O_RDONLY, O_WRONLY, SEEK_SET},
+    pipe::make_pipe,
+    vfs::FsError,
+};
+use os::init::{sys_init, sys_run};
+use os::memory::memory_set::{
+    attr::MemoryAttr,
+    handler::{ByFrame, Delay},
+    MemorySet,
+};
+use os::memory::{alloc_frame, alloc_frames, free_frame_count};
 
 global_asm!(include_str!("boot/entry64.asm"));
 
@@ -39,11 +53,11 @@ fn read_invalid_test() {
 }
 
 #[no_mangle] // don't mangle the name of this function
-pub extern "C" fn run_main() -> ! {
+pub extern "C" fn run_main(hartid: usize, dtb: usize) -> ! {
     extern "C" {
         fn end();
     }
-    sys_init();
+    sys_init(hartid, dtb);
 
     //frame_allocating_test();
     //unsafe {
@@ -52,6 +66,12 @@ pub extern "C" fn run_main() -> ! {
     //write_readonly_test();
     //execute_unexecutable_test();
     //read_invalid_test();
+    memory_set_drop_test();
+    frame_pressure_test();
+    block_device_test();
+    vfs_test();
+    pipe_test();
+    sys_run();
     loop {}
 }
 
@@ -61,7 +81,122 @@ fn frame_allocating_test() {
     println!("alloc {:x?}", f);
     println!("alloc {:x?}", alloc_frame());
     println!("dealloc {:x?}", f);
-    dealloc_frame(f.unwrap());
+    drop(f);
     println!("alloc {:x?}", alloc_frame());
     println!("alloc {:x?}", alloc_frame());
 }
+
+// 反复创建、复制并销毁地址空间，检查物理页是否全部归还
+fn memory_set_drop_test() {
+    let free = free_frame_count();
+    for _ in 0..100 {
+        let mut memory_set = MemorySet::new();
+        memory_set.push(
+            0x10000,
+            0x20000,
+            MemoryAttr::new().set_user(),
+            ByFrame::new(),
+            None,
+        );
+        memory_set.push(
+            0x40000,
+            0x80000,
+            MemoryAttr::new().set_user(),
+            Delay::new(),
+            None,
+        );
+        // 模拟访问延迟分配的页，触发异常的地址不一定按页对齐
+        assert!(memory_set.handle_page_fault(0x41008));
+        let mut child = memory_set.clone_cow();
+        // 子地址空间写入共享的页，触发写时复制
+        assert!(child.handle_page_fault(0x41010));
+        drop(child);
+        drop(memory_set);
+    }
+    assert_eq!(free_frame_count(), free);
+    println!("memory_set_drop_test passed!");
+}
+
+// 连续分配的对齐与物理内存耗尽时的行为
+fn frame_pressure_test() {
+    let free = free_frame_count();
+    {
+        let frames = alloc_frames(16, 4).unwrap();
+        assert_eq!(frames.start_address().as_usize() % (16 * 4096), 0);
+        assert_eq!(free_frame_count(), free - 16);
+        // 耗尽所有物理页
+        let mut all = Vec::new();
+        while let Some(frame) = alloc_frame() {
+            all.push(frame);
+        }
+        assert_eq!(free_frame_count(), 0);
+        assert!(alloc_frames(1, 0).is_none());
+        println!("allocated {} frames before exhaustion", all.len());
+    }
+    assert_eq!(free_frame_count(), free);
+    println!("frame_pressure_test passed!");
+}
+
+// 启动阶段没有线程，以轮询的方式读写块设备的最后一块，结束后恢复原来的内容
+fn block_device_test() {
+    let device = match block_device() {
+        Some(device) => device,
+        None => {
+            println!("no block device, skip block_device_test");
+            return;
+        }
+    };
+    let id = device.block_count() - 1;
+    let mut origin = [0u8; BLOCK_SIZE];
+    let mut buf = [0u8; BLOCK_SIZE];
+    device.read_block(id, &mut origin).unwrap();
+    let data: Vec<u8> = (0..BLOCK_SIZE).map(|i| (i * 3) as u8).collect();
+    device.write_block(id, &data).unwrap();
+    device.read_block(id, &mut buf).unwrap();
+    assert!(buf[..] == data[..]);
+    device.write_block(id, &origin).unwrap();
+    device.read_block(id, &mut buf).unwrap();
+    assert!(buf[..] == origin[..]);
+    // 超出范围的块号返回错误
+    assert!(device.read_block(device.block_count(), &mut buf).is_err());
+    println!("block_device_test passed!");
+}
+
+// 通过挂载表打开控制台，并检查文件描述符的分配与回收
+fn vfs_test() {
+    let console = fs::open("/dev/console", O_WRONLY).expect("open console failed!");
+    console
+        .write(b"vfs test: hello from /dev/console\n")
+        .unwrap();
+    assert_eq!(console.seek(0, SEEK_SET), Err(FsError::NotSeekable));
+    assert_eq!(
+        fs::open("/dev/no_such_device", O_RDONLY).err(),
+        Some(FsError::NotFound)
+    );
+    let mut files = FdTable::with_stdio();
+    let fd = files.dup(1).unwrap();
+    assert_eq!(fd, 3);
+    // 关闭后空出的最小文件描述符被再次使用
+    files.close(0).unwrap();
+    assert_eq!(files.add(console).unwrap(), 0);
+    assert_eq!(files.close(fd), Ok(()));
+    assert_eq!(files.close(fd), Err(FsError::BadFd));
+    println!("vfs test passed!");
+}
+
+// 启动阶段没有线程可以睡眠，只检查不需要等待的情况
+fn pipe_test() {
+    let (reader, writer) = make_pipe();
+    let mut buf = [0u8; 8];
+    assert_eq!(writer.write(b"pipe"), Ok(4));
+    assert_eq!(reader.read(&mut buf), Ok(4));
+    assert!(&buf[..4] == b"pipe");
+    assert_eq!(reader.write(b"x"), Err(FsError::BadFd));
+    // 写端关闭后读到 EOF
+    drop(writer);
+    assert_eq!(reader.read(&mut buf), Ok(0));
+    let (reader, writer) = make_pipe();
+    drop(reader);
+    assert_eq!(writer.write(b"x"), Err(FsError::BrokenPipe));
+    println!("pipe test passed!");
+}
@@ -1,8 +1,11 @@
 use crate::consts::MAX_PHYSICAL_PAGES;
 use spin::Mutex;
 
+// 线段树的每个结点记录其子树中空闲物理页的个数
 pub struct SegmentTreeAllocator {
-    a: [u8; MAX_PHYSICAL_PAGES << 1],
+    a: [u32; MAX_PHYSICAL_PAGES << 1],
+    // 每个物理页被引用的次数，用于写时复制时共享物理页
+    ref_count: [u16; MAX_PHYSICAL_PAGES],
     m: usize,
     n: usize,
     offset: usize,
@@ -17,53 +20,126 @@ impl SegmentTreeAllocator {
             self.m = self.m << 1;
         }
         for i in (1..(self.m << 1)) {
-            self.a[i] = 1;
+            self.a[i] = 0;
         }
         for i in (1..self.n) {
-            self.a[self.m + i] = 0;
+            self.a[self.m + i] = 1;
         }
         for i in (1..self.m).rev() {
-            self.a[i] = self.a[i << 1] & self.a[(i << 1) | 1];
+            self.a[i] = self.a[i << 1] + self.a[(i << 1) | 1];
         }
     }
 
-    pub fn alloc(&mut self) -> usize {
-        // assume that we never run out of physical memory
-        if self.a[1] == 1 {
-            panic!("physical memory depleted!");
+    // 设置叶子 i 的状态并自底向上更新
+    fn set(&mut self, i: usize, free: bool) {
+        let mut p = i + self.m;
+        self.a[p] = free as u32;
+        p >>= 1;
+        while p > 0 {
+            self.a[p] = self.a[p << 1] + self.a[(p << 1) | 1];
+            p >>= 1;
+        }
+    }
+
+    // 叶子 [l, r) 中空闲物理页的个数
+    fn query(&self, l: usize, r: usize) -> usize {
+        let mut l = l + self.m;
+        let mut r = r + self.m;
+        let mut sum = 0;
+        while l < r {
+            if l & 1 == 1 {
+                sum += self.a[l] as usize;
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                sum += self.a[r] as usize;
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        sum
+    }
+
+    // 分配一个物理页，物理内存耗尽时返回 None
+    pub fn alloc(&mut self) -> Option<usize> {
+        if self.a[1] == 0 {
+            return None;
         }
         let mut p = 1;
         while p < self.m {
-            if self.a[p << 1] == 0 {
+            if self.a[p << 1] > 0 {
                 p = p << 1;
             } else {
                 p = (p << 1) | 1;
             }
         }
-        let result = p + self.offset - self.m;
-        self.a[p] = 1;
-        p >>= 1;
-        while p > 0 {
-            self.a[p] = self.a[p << 1] & self.a[(p << 1) | 1];
-            p >>= 1;
+        let i = p - self.m;
+        self.set(i, false);
+        self.ref_count[i] = 1;
+        Some(i + self.offset)
+    }
+
+    // 分配 count 个物理上连续的物理页，起始物理页号按 2^align_log2 对齐
+    // 返回起始物理页号
+    pub fn alloc_contiguous(&mut self, count: usize, align_log2: usize) -> Option<usize> {
+        if count == 0 || self.free_count() < count {
+            return None;
+        }
+        let align = 1 << align_log2;
+        // 可分配的物理页号范围为 [offset + 1, offset + n)
+        let mut start = (self.offset + align) / align * align;
+        while start + count <= self.offset + self.n {
+            let l = start - self.offset;
+            if self.query(l, l + count) == count {
+                for i in l..l + count {
+                    self.set(i, false);
+                    self.ref_count[i] = 1;
+                }
+                return Some(start);
+            }
+            start += align;
         }
-        result
+        None
     }
 
+    // 减少一次引用，引用计数归零时才真正回收
     pub fn dealloc(&mut self, n: usize) {
-        let mut p = n + self.m - self.offset;
-        assert!(self.a[p] == 1);
-        self.a[p] = 0;
-        p >>= 1;
-        while p > 0 {
-            self.a[p] = self.a[p << 1] & self.a[(p << 1) | 1];
-            p >>= 1;
+        let i = n - self.offset;
+        assert!(self.ref_count[i] > 0, "dealloc a free frame!");
+        self.ref_count[i] -= 1;
+        if self.ref_count[i] > 0 {
+            return;
         }
+        assert!(self.a[i + self.m] == 0);
+        self.set(i, true);
+    }
+
+    pub fn dealloc_contiguous(&mut self, start: usize, count: usize) {
+        for n in start..start + count {
+            self.dealloc(n);
+        }
+    }
+
+    // 物理页多了一个共享者
+    pub fn add_ref(&mut self, n: usize) {
+        let i = n - self.offset;
+        assert!(self.ref_count[i] > 0, "share a free frame!");
+        self.ref_count[i] += 1;
+    }
+
+    pub fn ref_count(&self, n: usize) -> usize {
+        self.ref_count[n - self.offset] as usize
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.a[1] as usize
     }
 }
 
 pub static SEGMENT_TREE_ALLOCATOR: Mutex<SegmentTreeAllocator> = Mutex::new(SegmentTreeAllocator {
     a: [0; MAX_PHYSICAL_PAGES << 1],
+    ref_count: [0; MAX_PHYSICAL_PAGES],
     m: 0,
     n: 0,
     offset: 0,
@@ -12,17 +12,55 @@ pub struct MemoryArea {
 }
 
 impl MemoryArea {
-    pub fn map(&self, pt: &mut PageTableImpl) {
+    pub fn map(&mut self, pt: &mut PageTableImpl) {
         for page in PageRange::new(self.start, self.end) {
             self.handler.map(pt, page, &self.attr);
         }
     }
-    fn unmap(&self, pt: &mut PageTableImpl) {
+    fn unmap(&mut self, pt: &mut PageTableImpl) {
         for page in PageRange::new(self.start, self.end) {
             self.handler.unmap(pt, page);
         }
     }
 
+    // 将 [src, src + length) 中的数据复制到这段区域的开头
+    pub fn page_copy(&mut self, pt: &mut PageTableImpl, src: usize, length: usize) {
+        assert!(length <= self.end - self.start, "data out of memory area!");
+        let mut va = self.start;
+        let end = self.start + length;
+        while va < end {
+            // 每次最多复制到当前页的末尾
+            let page_end = (va / PAGE_SIZE + 1) * PAGE_SIZE;
+            let n = if page_end < end { page_end } else { end } - va;
+            self.handler.page_copy(pt, va, src + (va - self.start), n);
+            va += n;
+        }
+    }
+
+    pub fn contains(&self, va: usize) -> bool {
+        self.start <= va && va < self.end
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn attr(&self) -> &MemoryAttr {
+        &self.attr
+    }
+
+    pub fn handle_page_fault(&mut self, pt: &mut PageTableImpl, va: usize) -> bool {
+        self.handler.handle_page_fault(pt, va, &self.attr)
+    }
+
+    // 在新页表 pt 中复制这段区域在 src_pt 中的映射
+    // 在 clone 得到的新区域上调用
+    pub fn clone_map(&mut self, pt: &mut PageTableImpl, src_pt: &mut PageTableImpl) {
+        for page in PageRange::new(self.start, self.end) {
+            self.handler.clone_map(pt, src_pt, page, &self.attr);
+        }
+    }
+
     pub fn is_overlap_with(&self, start_addr: usize, end_addr: usize) -> bool {
         let p1 = self.start / PAGE_SIZE;
         let p2 = (self.end - 1) / PAGE_SIZE + 1;
@@ -29,6 +29,14 @@ impl MemoryAttr {
         self
     }
 
+    pub fn user(&self) -> bool {
+        self.user
+    }
+
+    pub fn writable(&self) -> bool {
+        !self.readonly
+    }
+
     pub fn apply(&self, entry: &mut PageEntry) {
         entry.set_present(true);
         entry.set_user(self.user);
@@ -1,13 +1,112 @@
 use super::attr::MemoryAttr;
-use crate::memory::alloc_frame;
+use crate::consts::PAGE_SIZE;
 use crate::memory::paging::PageTableImpl;
+use crate::memory::{access_pa_via_va, alloc_frame, FrameTracker};
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use core::fmt::Debug;
 
-pub trait MemoryHandler: Debug + 'static {
+pub trait MemoryHandler: Debug + Send + 'static {
     fn box_clone(&self) -> Box<dyn MemoryHandler>;
-    fn map(&self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr);
-    fn unmap(&self, pt: &mut PageTableImpl, va: usize);
+    fn map(&mut self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr);
+    fn unmap(&mut self, pt: &mut PageTableImpl, va: usize);
+    // 将 src 开始的 length 字节复制到虚拟地址 va 处
+    // 要求 [va, va + length) 不跨页
+    fn page_copy(&mut self, pt: &mut PageTableImpl, va: usize, src: usize, length: usize);
+    // 在新页表 pt 中建立与 src_pt 中相同的 va 映射，用于复制地址空间
+    // 在 box_clone 得到的新 handler 上调用，此时物理页已经被新 handler 共享
+    fn clone_map(
+        &mut self,
+        pt: &mut PageTableImpl,
+        src_pt: &mut PageTableImpl,
+        va: usize,
+        attr: &MemoryAttr,
+    );
+    // 处理 va 处的缺页异常，返回是否成功处理
+    fn handle_page_fault(
+        &mut self,
+        _pt: &mut PageTableImpl,
+        _va: usize,
+        _attr: &MemoryAttr,
+    ) -> bool {
+        false
+    }
+}
+
+// 分配一个清零的物理页
+// 新分配的物理页内容不确定，清零后才能作为 .bss 或栈使用
+fn alloc_zeroed_frame() -> FrameTracker {
+    let frame = alloc_frame().expect("alloc_frame failed!");
+    unsafe {
+        core::ptr::write_bytes(
+            access_pa_via_va(frame.start_address().as_usize()) as *mut u8,
+            0,
+            PAGE_SIZE,
+        );
+    }
+    frame
+}
+
+// 新旧页表共享 va 处的物理页 frame，双方都设为只读，等到写入时再复制
+fn share_cow(
+    pt: &mut PageTableImpl,
+    src_pt: &mut PageTableImpl,
+    va: usize,
+    attr: &MemoryAttr,
+    frame: &FrameTracker,
+) {
+    if attr.writable() {
+        let src_entry = src_pt.get_entry(va).expect("get entry error!");
+        src_entry.set_writable(false);
+        src_entry.update();
+    }
+    let entry = pt.map(va, frame.start_address().as_usize());
+    attr.apply(entry);
+    entry.set_writable(false);
+}
+
+// 写入一个共享的只读页时，为其复制一份私有的物理页
+fn copy_on_write(
+    pt: &mut PageTableImpl,
+    va: usize,
+    attr: &MemoryAttr,
+    frames: &mut BTreeMap<usize, FrameTracker>,
+) -> bool {
+    if !attr.writable() {
+        return false;
+    }
+    let entry = match pt.get_entry(va) {
+        Some(entry) => entry,
+        None => return false,
+    };
+    if !entry.present() || entry.writable() {
+        return false;
+    }
+    let shared = match frames.get(&va) {
+        Some(frame) => frame.ref_count() > 1,
+        None => return false,
+    };
+    // 已经没有其他共享者，直接恢复写权限即可
+    if shared {
+        let frame = alloc_frame().expect("alloc_frame failed!");
+        let pa = frame.start_address().as_usize();
+        unsafe {
+            copy_to_pa(pa, access_pa_via_va(entry.target()), PAGE_SIZE);
+        }
+        entry.set_target(pa);
+        // 替换掉旧的 FrameTracker，释放对共享物理页的引用
+        frames.insert(va, frame);
+    }
+    entry.set_writable(true);
+    entry.update();
+    true
+}
+
+// 通过物理地址对应的内核虚拟地址进行复制，不依赖当前生效的页表
+unsafe fn copy_to_pa(pa: usize, src: usize, length: usize) {
+    let dst = core::slice::from_raw_parts_mut(access_pa_via_va(pa) as *mut u8, length);
+    let src = core::slice::from_raw_parts(src as *const u8, length);
+    dst.copy_from_slice(src);
 }
 
 impl Clone for Box<dyn MemoryHandler> {
@@ -30,19 +129,38 @@ impl MemoryHandler for Linear {
     fn box_clone(&self) -> Box<dyn MemoryHandler> {
         Box::new(self.clone())
     }
-    fn map(&self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) {
+    fn map(&mut self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) {
         attr.apply(pt.map(va, va - self.offset));
     }
-    fn unmap(&self, pt: &mut PageTableImpl, va: usize) {
+    fn unmap(&mut self, pt: &mut PageTableImpl, va: usize) {
         pt.unmap(va);
     }
+    fn page_copy(&mut self, _pt: &mut PageTableImpl, va: usize, src: usize, length: usize) {
+        unsafe {
+            copy_to_pa(va - self.offset, src, length);
+        }
+    }
+    fn clone_map(
+        &mut self,
+        pt: &mut PageTableImpl,
+        _src_pt: &mut PageTableImpl,
+        va: usize,
+        attr: &MemoryAttr,
+    ) {
+        self.map(pt, va, attr);
+    }
 }
 
+// 按页分配物理页，物理页归 handler 所有，随 handler 一起释放
 #[derive(Debug, Clone)]
-pub struct ByFrame;
+pub struct ByFrame {
+    frames: BTreeMap<usize, FrameTracker>,
+}
 impl ByFrame {
     pub fn new() -> Self {
-        ByFrame {}
+        ByFrame {
+            frames: BTreeMap::new(),
+        }
     }
 }
 impl MemoryHandler for ByFrame {
@@ -50,13 +168,113 @@ impl MemoryHandler for ByFrame {
         Box::new(self.clone())
     }
 
-    fn map(&self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) {
-        let frame = alloc_frame().expect("alloc_frame failed!");
-        let pa = frame.start_address().as_usize();
-        attr.apply(pt.map(va, pa));
+    fn map(&mut self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) {
+        let frame = alloc_zeroed_frame();
+        attr.apply(pt.map(va, frame.start_address().as_usize()));
+        self.frames.insert(va, frame);
     }
 
-    fn unmap(&self, pt: &mut PageTableImpl, va: usize) {
+    fn unmap(&mut self, pt: &mut PageTableImpl, va: usize) {
         pt.unmap(va);
+        self.frames.remove(&va);
+    }
+
+    fn page_copy(&mut self, pt: &mut PageTableImpl, va: usize, src: usize, length: usize) {
+        let pa = pt.get_entry(va).expect("get pa error!").target() + va % PAGE_SIZE;
+        unsafe {
+            copy_to_pa(pa, src, length);
+        }
+    }
+
+    fn clone_map(
+        &mut self,
+        pt: &mut PageTableImpl,
+        src_pt: &mut PageTableImpl,
+        va: usize,
+        attr: &MemoryAttr,
+    ) {
+        let frame = self.frames.get(&va).expect("frame not exist!");
+        share_cow(pt, src_pt, va, attr, frame);
+    }
+
+    fn handle_page_fault(&mut self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) -> bool {
+        copy_on_write(pt, va, attr, &mut self.frames)
+    }
+}
+
+// 延迟分配：映射时只写入一个无效的页表项，第一次访问触发缺页异常时才分配物理页
+#[derive(Debug, Clone)]
+pub struct Delay {
+    frames: BTreeMap<usize, FrameTracker>,
+}
+impl Delay {
+    pub fn new() -> Self {
+        Delay {
+            frames: BTreeMap::new(),
+        }
+    }
+}
+impl MemoryHandler for Delay {
+    fn box_clone(&self) -> Box<dyn MemoryHandler> {
+        Box::new(self.clone())
+    }
+
+    fn map(&mut self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) {
+        // 保留 attr 设置的权限，只清除有效位
+        let entry = pt.map(va, 0);
+        attr.apply(entry);
+        entry.set_present(false);
+        entry.update();
+    }
+
+    fn unmap(&mut self, pt: &mut PageTableImpl, va: usize) {
+        pt.unmap(va);
+        self.frames.remove(&va);
+    }
+
+    fn page_copy(&mut self, pt: &mut PageTableImpl, va: usize, src: usize, length: usize) {
+        // 需要写入数据的页立即分配
+        self.alloc_on_fault(pt, va);
+        let pa = pt.get_entry(va).expect("get pa error!").target() + va % PAGE_SIZE;
+        unsafe {
+            copy_to_pa(pa, src, length);
+        }
+    }
+
+    fn clone_map(
+        &mut self,
+        pt: &mut PageTableImpl,
+        src_pt: &mut PageTableImpl,
+        va: usize,
+        attr: &MemoryAttr,
+    ) {
+        match self.frames.get(&va) {
+            Some(frame) => share_cow(pt, src_pt, va, attr, frame),
+            // 尚未分配的页在新页表中同样延迟分配
+            None => self.map(pt, va, attr),
+        }
+    }
+
+    fn handle_page_fault(&mut self, pt: &mut PageTableImpl, va: usize, attr: &MemoryAttr) -> bool {
+        self.alloc_on_fault(pt, va) || copy_on_write(pt, va, attr, &mut self.frames)
+    }
+}
+
+impl Delay {
+    // 为尚未分配的页分配物理页，页已经存在时返回 false
+    fn alloc_on_fault(&mut self, pt: &mut PageTableImpl, va: usize) -> bool {
+        let entry = match pt.get_entry(va) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        if entry.present() {
+            return false;
+        }
+        let frame = alloc_zeroed_frame();
+        entry.set_target(frame.start_address().as_usize());
+        entry.set_present(true);
+        entry.update();
+        self.frames.insert(va, frame);
+        true
     }
 }
@@ -3,8 +3,9 @@ pub mod attr;
 pub mod handler;
 
 use crate::consts::*;
-use crate::memory::access_pa_via_va;
+use crate::dtb;
 use crate::memory::paging::PageTableImpl;
+use crate::memory::{access_pa_via_va, physical_memory_end};
 use alloc::{boxed::Box, vec::Vec};
 use area::MemoryArea;
 use attr::MemoryAttr;
@@ -22,13 +23,42 @@ impl MemorySet {
         end: usize,
         attr: MemoryAttr,
         handler: impl MemoryHandler,
+        data: Option<(usize, usize)>,
     ) {
         assert!(start <= end, "invalid memory area!");
         assert!(self.test_free_area(start, end), "memory area overlap!");
-        let area = MemoryArea::new(start, end, Box::new(handler), attr);
+        let mut area = MemoryArea::new(start, end, Box::new(handler), attr);
         area.map(&mut self.page_table);
+        // 若提供了数据 (起始地址, 长度)，将其复制到新映射的区域中
+        if let Some((src, length)) = data {
+            area.page_copy(&mut self.page_table, src, length);
+        }
         self.areas.push(area);
     }
+    // 找到 va 所在的区域，交给其 handler 处理缺页异常
+    // va 是触发异常的地址，handler 以页的起始地址记录物理页，因此先向下对齐
+    pub fn handle_page_fault(&mut self, va: usize) -> bool {
+        let va = va & !(PAGE_SIZE - 1);
+        let page_table = &mut self.page_table;
+        match self.areas.iter_mut().find(|area| area.contains(va)) {
+            Some(area) => area.handle_page_fault(page_table, va),
+            None => false,
+        }
+    }
+    // [start, end) 是否完全被用户态可以访问的区域覆盖，write 为 true 时还要求这些区域可写
+    pub fn check_user_range(&self, start: usize, end: usize, write: bool) -> bool {
+        let mut va = start;
+        while va < end {
+            let area = self.areas.iter().find(|area| {
+                area.contains(va) && area.attr().user() && (!write || area.attr().writable())
+            });
+            match area {
+                Some(area) => va = area.end(),
+                None => return false,
+            }
+        }
+        true
+    }
     fn test_free_area(&self, start: usize, end: usize) -> bool {
         self.areas
             .iter()
@@ -38,6 +68,19 @@ impl MemorySet {
     pub unsafe fn activate(&self) {
         self.page_table.activate();
     }
+    // 以写时复制的方式复制该地址空间：物理页由父子双方共享，任何一方写入时才复制
+    pub fn clone_cow(&mut self) -> MemorySet {
+        let mut page_table = PageTableImpl::new_bare();
+        // 复制区域时各 handler 中的 FrameTracker 也被复制，即共享了物理页
+        let mut areas = self.areas.clone();
+        for area in areas.iter_mut() {
+            area.clone_map(&mut page_table, &mut self.page_table);
+        }
+        MemorySet { areas, page_table }
+    }
+    pub fn token(&self) -> usize {
+        self.page_table.token()
+    }
     pub fn new() -> Self {
         let mut memory_set = MemorySet {
             areas: Vec::new(),
@@ -66,6 +109,7 @@ impl MemorySet {
             etext as usize,
             MemoryAttr::new().set_readonly().set_execute(),
             Linear::new(offset),
+            None,
         );
         // .rodata R
         self.push(
@@ -73,6 +117,7 @@ impl MemorySet {
             erodata as usize,
             MemoryAttr::new().set_readonly(),
             Linear::new(offset),
+            None,
         );
         // .data R|W
         self.push(
@@ -80,6 +125,7 @@ impl MemorySet {
             edata as usize,
             MemoryAttr::new(),
             Linear::new(offset),
+            None,
         );
         // .bss R|W
         self.push(
@@ -87,13 +133,25 @@ impl MemorySet {
             ebss as usize,
             MemoryAttr::new(),
             Linear::new(offset),
+            None,
         );
         // 物理内存 R|W
         self.push(
             (end as usize / PAGE_SIZE + 1) * PAGE_SIZE,
-            access_pa_via_va(PHYSICAL_MEMORY_END),
+            access_pa_via_va(physical_memory_end()),
             MemoryAttr::new(),
             Linear::new(offset),
+            None,
         );
+        // 设备的 MMIO 区域 R|W
+        for device in dtb::board().mmio_devices() {
+            self.push(
+                access_pa_via_va(device.base),
+                access_pa_via_va(device.base + device.size),
+                MemoryAttr::new(),
+                Linear::new(offset),
+                None,
+            );
+        }
     }
 }
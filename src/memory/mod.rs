@@ -4,23 +4,108 @@ pub mod paging;
 
 use crate::consts::*;
 use buddy_system_allocator::LockedHeap;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use frame_allocator::SEGMENT_TREE_ALLOCATOR as FRAME_ALLOCATOR;
 use memory_set::{attr::MemoryAttr, handler::Linear, MemorySet};
 use riscv::addr::{Frame, Page, PhysAddr, VirtAddr};
+use spin::Mutex;
 
+// 物理内存的结束地址，由设备树得到
+static PHYSICAL_MEMORY_END: AtomicUsize = AtomicUsize::new(0);
+
+pub fn physical_memory_end() -> usize {
+    PHYSICAL_MEMORY_END.load(Ordering::Relaxed)
+}
+
+// 可分配的物理页号范围为 [l, r)
 pub fn init(l: usize, r: usize) {
+    PHYSICAL_MEMORY_END.store(r << 12, Ordering::Relaxed);
     FRAME_ALLOCATOR.lock().init(l, r);
     init_heap();
     kernel_remap();
     println!("++++ setup memory!    ++++");
 }
 
-pub fn alloc_frame() -> Option<Frame> {
-    Some(Frame::of_ppn(FRAME_ALLOCATOR.lock().alloc()))
+// 物理页的所有权，被 drop 时自动归还物理页
+// clone 得到的 FrameTracker 与原来的共享同一物理页，最后一个被 drop 时物理页才被回收
+#[derive(Debug)]
+pub struct FrameTracker(Frame);
+
+impl FrameTracker {
+    pub fn frame(&self) -> Frame {
+        self.0
+    }
+    pub fn start_address(&self) -> PhysAddr {
+        self.0.start_address()
+    }
+    pub fn number(&self) -> usize {
+        self.0.number()
+    }
+    // 共享这个物理页的 FrameTracker 个数
+    pub fn ref_count(&self) -> usize {
+        FRAME_ALLOCATOR.lock().ref_count(self.0.number())
+    }
+}
+
+impl Clone for FrameTracker {
+    fn clone(&self) -> Self {
+        FRAME_ALLOCATOR.lock().add_ref(self.0.number());
+        FrameTracker(self.0)
+    }
+}
+
+impl Drop for FrameTracker {
+    fn drop(&mut self) {
+        FRAME_ALLOCATOR.lock().dealloc(self.0.number());
+    }
+}
+
+// 物理内存耗尽时返回 None
+pub fn alloc_frame() -> Option<FrameTracker> {
+    FRAME_ALLOCATOR
+        .lock()
+        .alloc()
+        .map(|ppn| FrameTracker(Frame::of_ppn(ppn)))
+}
+
+// 物理上连续的若干物理页，被 drop 时一起归还
+#[derive(Debug)]
+pub struct ContiguousFrames {
+    start: Frame,
+    count: usize,
+}
+
+impl ContiguousFrames {
+    pub fn start_address(&self) -> PhysAddr {
+        self.start.start_address()
+    }
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Drop for ContiguousFrames {
+    fn drop(&mut self) {
+        FRAME_ALLOCATOR
+            .lock()
+            .dealloc_contiguous(self.start.number(), self.count);
+    }
 }
 
-pub fn dealloc_frame(f: Frame) {
-    FRAME_ALLOCATOR.lock().dealloc(f.number())
+// 分配 count 个物理上连续的物理页，起始地址按 2^align_log2 个页对齐
+pub fn alloc_frames(count: usize, align_log2: usize) -> Option<ContiguousFrames> {
+    FRAME_ALLOCATOR
+        .lock()
+        .alloc_contiguous(count, align_log2)
+        .map(|ppn| ContiguousFrames {
+            start: Frame::of_ppn(ppn),
+            count,
+        })
+}
+
+// 当前空闲的物理页个数
+pub fn free_frame_count() -> usize {
+    FRAME_ALLOCATOR.lock().free_count()
 }
 
 fn init_heap() {
@@ -36,6 +121,9 @@ pub fn access_pa_via_va(pa: usize) -> usize {
     pa + PHYSICAL_MEMORY_OFFSET
 }
 
+// 内核地址空间，其页表在内核运行期间必须一直有效，不能被释放
+pub static KERNEL_MEMORY_SET: Mutex<Option<MemorySet>> = Mutex::new(None);
+
 pub fn kernel_remap() {
     let mut memory_set = MemorySet::new();
 
@@ -48,11 +136,24 @@ pub fn kernel_remap() {
         bootstacktop as usize,
         MemoryAttr::new(),
         Linear::new(PHYSICAL_MEMORY_OFFSET),
+        None,
     );
 
     unsafe {
         memory_set.activate();
     }
+    *KERNEL_MEMORY_SET.lock() = Some(memory_set);
+}
+
+// 其他 hart 启动时仍在使用启动页表，切换到已经建立好的内核页表
+pub fn init_hart() {
+    unsafe {
+        KERNEL_MEMORY_SET
+            .lock()
+            .as_ref()
+            .expect("kernel memory set is not initialized!")
+            .activate();
+    }
 }
 
 #[global_allocator]
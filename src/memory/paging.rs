@@ -1,10 +1,11 @@
 use crate::consts::*;
-use crate::memory::{access_pa_via_va, alloc_frame, dealloc_frame};
+use crate::memory::{access_pa_via_va, alloc_frame, FrameTracker};
+use alloc::vec::Vec;
 use riscv::addr::*;
 use riscv::asm::{sfence_vma, sfence_vma_all};
 use riscv::paging::{
-    FrameAllocator, FrameDeallocator, Mapper, PageTable as PageTableEntryArray, PageTableEntry,
-    PageTableFlags as EF, Rv39PageTable,
+    FrameAllocator, Mapper, PageTable as PageTableEntryArray, PageTableEntry, PageTableFlags as EF,
+    Rv39PageTable,
 };
 use riscv::register::satp;
 
@@ -69,23 +70,23 @@ impl PageEntry {
     }
 }
 
-struct FrameAllocatorForPaging;
+// 为页表分配中间级页表所在的物理页，并将其记录下来
+struct FrameAllocatorForPaging<'a>(&'a mut Vec<FrameTracker>);
 
-impl FrameAllocator for FrameAllocatorForPaging {
+impl FrameAllocator for FrameAllocatorForPaging<'_> {
     fn alloc(&mut self) -> Option<Frame> {
-        alloc_frame()
-    }
-}
-
-impl FrameDeallocator for FrameAllocatorForPaging {
-    fn dealloc(&mut self, frame: Frame) {
-        dealloc_frame(frame)
+        let frame = alloc_frame()?;
+        let ret = frame.frame();
+        self.0.push(frame);
+        Some(ret)
     }
 }
 
 pub struct PageTableImpl {
     page_table: Rv39PageTable<'static>,
-    root_frame: Frame,
+    root_frame: FrameTracker,
+    // 中间级页表占用的物理页，随页表一起释放
+    table_frames: Vec<FrameTracker>,
     entry: Option<PageEntry>,
 }
 
@@ -99,6 +100,7 @@ impl PageTableImpl {
         PageTableImpl {
             page_table: Rv39PageTable::new(table, PHYSICAL_MEMORY_OFFSET),
             root_frame: frame,
+            table_frames: Vec::new(),
             entry: None,
         }
     }
@@ -108,7 +110,12 @@ impl PageTableImpl {
         let page = Page::of_addr(VirtAddr::new(va));
         let frame = Frame::of_addr(PhysAddr::new(pa));
         self.page_table
-            .map_to(page, frame, flags, &mut FrameAllocatorForPaging)
+            .map_to(
+                page,
+                frame,
+                flags,
+                &mut FrameAllocatorForPaging(&mut self.table_frames),
+            )
             .unwrap()
             .flush();
         self.get_entry(va).expect("fail to get an entry!")
@@ -120,7 +127,7 @@ impl PageTableImpl {
         flush.flush();
     }
 
-    fn get_entry(&mut self, va: usize) -> Option<&mut PageEntry> {
+    pub fn get_entry(&mut self, va: usize) -> Option<&mut PageEntry> {
         let page = Page::of_addr(VirtAddr::new(va));
         if let Ok(e) = self.page_table.ref_entry(page.clone()) {
             let e = unsafe { &mut *(e as *mut PageTableEntry) };
@@ -0,0 +1,53 @@
+use crate::memory::memory_set::{attr::MemoryAttr, handler::ByFrame, MemorySet};
+use xmas_elf::{
+    program::{Flags, SegmentData, Type},
+    ElfFile,
+};
+
+pub trait ElfExt {
+    fn make_memory_set(&self) -> MemorySet;
+}
+
+impl ElfExt for ElfFile<'_> {
+    // 新建一个包含内核映射的 MemorySet，并将 ELF 中所有 LOAD 段映射进去
+    fn make_memory_set(&self) -> MemorySet {
+        let mut memory_set = MemorySet::new();
+        for ph in self.program_iter() {
+            if ph.get_type() != Ok(Type::Load) {
+                continue;
+            }
+            let vaddr = ph.virtual_addr() as usize;
+            let mem_size = ph.mem_size() as usize;
+            let data = match ph.get_data(self).unwrap() {
+                SegmentData::Undefined(data) => data,
+                _ => unreachable!(),
+            };
+            // mem_size 超出文件中数据的部分 (.bss) 由 ByFrame 清零
+            memory_set.push(
+                vaddr,
+                vaddr + mem_size,
+                ph.flags().to_attr(),
+                ByFrame::new(),
+                Some((data.as_ptr() as usize, data.len())),
+            );
+        }
+        memory_set
+    }
+}
+
+trait ToMemoryAttr {
+    fn to_attr(&self) -> MemoryAttr;
+}
+
+impl ToMemoryAttr for Flags {
+    fn to_attr(&self) -> MemoryAttr {
+        let mut attr = MemoryAttr::new().set_user();
+        if !self.is_write() {
+            attr = attr.set_readonly();
+        }
+        if self.is_execute() {
+            attr = attr.set_execute();
+        }
+        attr
+    }
+}
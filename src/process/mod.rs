@@ -1,17 +1,37 @@
+mod elf;
+pub mod programs;
 pub mod scheduler;
 pub mod thread_pool;
 
 pub type Tid = usize;
 
-use crate::alloc::alloc::{alloc, dealloc, Layout};
 use crate::alloc::boxed::Box;
+use crate::alloc::sync::Arc;
+use crate::alloc::vec::Vec;
 use crate::consts::*;
-use crate::context::Context;
+use crate::context::{Context, StackFrame};
+use crate::drivers::block::{block_device, BLOCK_SIZE};
+use crate::dtb;
+use crate::fs::{self, file::FdTable};
+use crate::interrupt::timer::{self, clock_freq, get_cycle};
+use crate::interrupt::{disable_and_store, restore};
+use crate::memory::memory_set::{attr::MemoryAttr, handler::Delay, MemorySet};
+use crate::memory::{access_pa_via_va, alloc_frames, ContiguousFrames};
+use crate::sync::Condvar;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use elf::ElfExt;
 use riscv::register::satp;
+use spin::{Mutex, MutexGuard, Once};
+use xmas_elf::{header, ElfFile};
 
 pub struct Thread {
     pub context: Context,
     pub kstack: KernelStack,
+    // 用户线程的地址空间，内核线程为 None
+    pub vm: Option<Arc<Mutex<MemorySet>>>,
+    // 文件描述符表，只由线程自身访问
+    pub files: FdTable,
 }
 
 impl Thread {
@@ -28,9 +48,66 @@ impl Thread {
                 // 内核线程共享内核资源，因此用目前的 satp 即可
                 context: Context::new_kernel_thread(entry, kstack_.top(), satp::read().bits()),
                 kstack: kstack_,
+                vm: None,
+                files: FdTable::with_stdio(),
             })
         }
     }
+
+    // 从 ELF 镜像新建一个用户线程
+    pub fn new_user(elf_data: &[u8]) -> Box<Thread> {
+        let elf = ElfFile::new(elf_data).expect("failed to analyse elf!");
+        match elf.header.pt2.type_().as_type() {
+            header::Type::Executable => {}
+            header::Type::SharedObject => panic!("shared object is not supported!"),
+            _ => panic!("unsupported elf type!"),
+        }
+        let entry_addr = elf.header.pt2.entry_point() as usize;
+        let mut vm = elf.make_memory_set();
+
+        // 为用户线程分配用户栈，用到哪一页才分配哪一页
+        let ustack_top = {
+            let (ustack_bottom, ustack_top) =
+                (USER_STACK_OFFSET, USER_STACK_OFFSET + USER_STACK_SIZE);
+            vm.push(
+                ustack_bottom,
+                ustack_top,
+                MemoryAttr::new().set_user(),
+                Delay::new(),
+                None,
+            );
+            ustack_top
+        };
+
+        let kstack = KernelStack::new();
+        Box::new(Thread {
+            context: unsafe {
+                Context::new_user_thread(entry_addr, ustack_top, kstack.top(), vm.token())
+            },
+            kstack,
+            vm: Some(Arc::new(Mutex::new(vm))),
+            files: FdTable::with_stdio(),
+        })
+    }
+    // 复制出一个子线程，地址空间以写时复制的方式共享
+    // sf 为当前线程陷入内核时保存的状态
+    pub fn fork(&self, sf: &StackFrame) -> Box<Thread> {
+        let vm = self
+            .vm
+            .as_ref()
+            .expect("kernel thread can not fork!")
+            .lock()
+            .clone_cow();
+        let kstack = KernelStack::new();
+        Box::new(Thread {
+            context: unsafe { Context::new_fork(sf, kstack.top(), vm.token()) },
+            kstack,
+            vm: Some(Arc::new(Mutex::new(vm))),
+            // 子线程继承打开的文件，与父线程共享偏移
+            files: self.files.clone(),
+        })
+    }
+
     // 为线程传入初始参数
     pub fn append_initial_arguments(&self, args: [usize; 3]) {
         unsafe {
@@ -42,36 +119,33 @@ impl Thread {
         Box::new(Thread {
             context: Context::null(),
             kstack: KernelStack::new_empty(),
+            vm: None,
+            files: FdTable::new(),
         })
     }
 }
 
-pub struct KernelStack(usize);
+// 内核栈直接使用物理上连续的物理页，通过内核的线性映射访问
+// 不占用有限的内核堆空间，随 KernelStack 一起释放
+pub struct KernelStack(Option<ContiguousFrames>);
 impl KernelStack {
     pub fn new() -> Self {
-        let bottom = unsafe {
-            alloc(Layout::from_size_align(KERNEL_STACK_SIZE, KERNEL_STACK_SIZE).unwrap()) as usize
-        };
-        KernelStack(bottom)
+        let frames =
+            alloc_frames(KERNEL_STACK_SIZE / PAGE_SIZE, 0).expect("alloc kernel stack failed!");
+        KernelStack(Some(frames))
     }
     pub fn new_empty() -> Self {
-        KernelStack(0)
+        KernelStack(None)
     }
     pub fn top(&self) -> usize {
-        self.0 + KERNEL_STACK_SIZE
-    }
-}
-
-impl Drop for KernelStack {
-    fn drop(&mut self) {
-        if self.0 != 0 {
-            unsafe {
-                dealloc(
-                    self.0 as _,
-                    Layout::from_size_align(KERNEL_STACK_SIZE, KERNEL_STACK_SIZE).unwrap(),
-                );
-            }
-        }
+        let bottom = access_pa_via_va(
+            self.0
+                .as_ref()
+                .expect("empty kernel stack!")
+                .start_address()
+                .as_usize(),
+        );
+        bottom + KERNEL_STACK_SIZE
     }
 }
 
@@ -82,48 +156,502 @@ pub extern "C" fn temp_thread(from_thread: &mut Thread, current_thread: &mut Thr
 }
 
 use scheduler::Processor;
-static CPU: Processor = Processor::new();
+use thread_pool::ThreadPool;
+pub use thread_pool::WaitStatus;
+
+// 每个 hart 对应一个 Processor，以 hartid 为下标
+static CPUS: [Processor; MAX_HARTS] = [
+    Processor::new(),
+    Processor::new(),
+    Processor::new(),
+    Processor::new(),
+    Processor::new(),
+    Processor::new(),
+    Processor::new(),
+    Processor::new(),
+];
+
+// 所有 hart 共享的线程池
+static THREAD_POOL: Once<Arc<Mutex<ThreadPool>>> = Once::new();
+
+// 当前 hart 的编号，启动时由 entry64.asm 保存在 tp 中
+#[inline(always)]
+pub fn hart_id() -> usize {
+    let id: usize;
+    unsafe {
+        llvm_asm!("mv $0, tp" : "=r"(id));
+    }
+    id
+}
+
+// 当前 hart 的 Processor
+fn cpu() -> &'static Processor {
+    &CPUS[hart_id()]
+}
+
+pub fn exit(code: usize) -> ! {
+    cpu().exit(code);
+}
+
+pub fn yield_now() {
+    cpu().yield_now();
+}
+
+pub fn current_tid() -> Tid {
+    cpu().current_tid()
+}
+
+// 不在线程中运行 (如启动阶段) 时返回 None
+pub fn try_current_tid() -> Option<Tid> {
+    cpu().try_current_tid()
+}
+
+// 当前线程进入睡眠，直到被 wakeup 唤醒
+// guard 保护着线程等待的条件，在标记睡眠之后才被释放
+// 调用前必须关闭异步中断
+pub fn sleep<T>(guard: MutexGuard<T>) {
+    cpu().sleep(guard);
+}
+
+// 唤醒睡眠中的线程 tid
+pub fn wakeup(tid: Tid) {
+    cpu().wakeup(tid);
+}
+
+// 在当前线程上执行 f，用于访问文件描述符表等线程自身的资源
+pub fn with_current<T>(f: impl FnOnce(&mut Thread) -> T) -> T {
+    cpu().with_current(f)
+}
 
-pub fn exit(code: usize) {
-    CPU.exit(code);
+// 等待并回收当前线程的子线程 target，target 为 None 时为任意子线程
+// block 为 false 时不等待，子线程都还没有退出则返回 WaitStatus::Running
+pub fn wait(target: Option<Tid>, block: bool) -> WaitStatus {
+    cpu().wait(target, block)
+}
+
+// 等待子线程 tid 退出，返回其退出码；tid 不是当前线程的子线程时返回 None
+pub fn join(tid: Tid) -> Option<usize> {
+    match wait(Some(tid), true) {
+        WaitStatus::Exited(_, code) => Some(code),
+        _ => None,
+    }
+}
+
+// 在新的内核线程中运行 f，f 的返回值作为线程的退出码
+// 新线程是当前线程的子线程，在启动阶段调用时则是 init 的子线程
+pub fn spawn<F>(f: F) -> JoinHandle
+where
+    F: FnOnce() -> usize + Send + 'static,
+{
+    // 胖指针无法放进一个寄存器，因此再装箱一次
+    let f: Box<Box<dyn FnOnce() -> usize + Send>> = Box::new(Box::new(f));
+    let thread = Thread::new_kernel(closure_thread as usize);
+    thread.append_initial_arguments([Box::into_raw(f) as usize, 0, 0]);
+    JoinHandle {
+        tid: cpu().add_thread(thread),
+    }
+}
+
+extern "C" fn closure_thread(f: usize) -> ! {
+    let f = unsafe { Box::from_raw(f as *mut Box<dyn FnOnce() -> usize + Send>) };
+    exit(f());
+}
+
+// spawn 得到的线程，不 join 就丢弃时由父线程或 init 回收
+pub struct JoinHandle {
+    tid: Tid,
+}
+
+impl JoinHandle {
+    pub fn tid(&self) -> Tid {
+        self.tid
+    }
+
+    // 等待线程退出，返回其退出码
+    // 只有调用 spawn 的线程才能 join，否则返回 None
+    pub fn join(self) -> Option<usize> {
+        join(self.tid)
+    }
+}
+
+pub fn is_child(tid: Tid) -> bool {
+    cpu().is_child(tid)
+}
+
+// 设置线程 tid 的优先级，数值越大得到的 CPU 时间越多，线程不存在时返回 false
+pub fn set_priority(tid: Tid, priority: usize) -> bool {
+    cpu().set_priority(tid, priority)
+}
+
+// 设置线程 tid 可以运行的 hart 集合，第 i 位对应 hart i
+// 线程不存在或者集合中没有参与调度的 hart 时返回 false
+pub fn set_affinity(tid: Tid, affinity: usize) -> bool {
+    cpu().set_affinity(tid, affinity)
+}
+
+// 将线程 tid 设为实时线程，每 period 个 tick 中至多运行 budget 个 tick，截止时间为周期结束
+// 只要有可运行的实时线程，普通线程就不会被调度
+// 线程不存在或者实时线程的总利用率将超过 1 时返回 false
+pub fn set_realtime(tid: Tid, period: u64, budget: u64) -> bool {
+    cpu().set_realtime(tid, period, budget)
+}
+
+// 实时线程 tid 错过截止时间的次数，不是实时线程时返回 None
+pub fn deadline_misses(tid: Tid) -> Option<usize> {
+    cpu().deadline_misses(tid)
+}
+
+// 复制当前线程，返回子线程的 tid
+pub fn fork(sf: &StackFrame) -> Tid {
+    cpu().fork(sf)
+}
+
+pub fn handle_page_fault(va: usize) -> bool {
+    cpu().handle_page_fault(va)
 }
 
 pub fn run() {
-    CPU.run();
+    cpu().run();
+}
+
+pub fn tick() {
+    cpu().tick();
+}
+
+use sched::{MlfqScheduler, RRScheduler, Scheduler, StrideScheduler};
+
+// 编译时通过环境变量 SCHEDULER 选择调度算法，例如 make run sched=rr
+// 每个 hart 的就绪队列各使用一个
+fn new_scheduler() -> Box<dyn Scheduler> {
+    match option_env!("SCHEDULER") {
+        Some("rr") => Box::new(RRScheduler::new(2)),
+        Some("mlfq") => Box::new(MlfqScheduler::new(1)),
+        _ => Box::new(StrideScheduler::new(2)),
+    }
 }
 
-use scheduler::RRScheduler;
-use thread_pool::ThreadPool;
 pub fn init() {
-    // 使用 Round Robin Scheduler
-    let scheduler = RRScheduler::new(2);
     // 新建线程池
-    let thread_pool = ThreadPool::new(100, Box::new(scheduler));
-    // 新建内核线程 idle ，其入口为 Processor::idle_main
-    let idle = Thread::new_kernel(Processor::idle_main as usize);
-    // 我们需要传入 CPU 的地址作为参数
-    idle.append_initial_arguments([&CPU as *const Processor as usize, 0, 0]);
-    // 初始化 CPU
-    CPU.init(idle, Box::new(thread_pool));
+    println!(
+        "using {} scheduler",
+        option_env!("SCHEDULER").unwrap_or("stride")
+    );
+    let thread_pool = ThreadPool::new(100, new_scheduler);
+    THREAD_POOL.call_once(|| Arc::new(Mutex::new(thread_pool)));
+    // 初始化启动 hart 的 CPU
+    init_hart();
+
+    // 最先加入 init 线程，之后由它回收启动阶段新建的线程以及所有孤儿
+    let init = cpu().add_thread(Thread::new_kernel(init_thread as usize));
+    THREAD_POOL.r#try().unwrap().lock().set_init(init);
+
+    // 先加入一个永不主动让出 CPU 的线程
+    // 只有时钟中断能将其切换出去，其他线程才有机会运行
+    // 它与 hello_thread 都只能在启动 hart 上运行，否则后者会在其他 hart 上运行，测试失去意义
+    let boot_hart = 1 << hart_id();
+    let spin = cpu().add_thread({
+        let thread = Thread::new_kernel(spin_thread as usize);
+        thread.append_initial_arguments([HELLO_THREADS, 0, 0]);
+        thread
+    });
+    assert!(set_affinity(spin, boot_hart));
 
     // 依次新建 5 个内核线程并加入调度单元
-    for i in 0..5 {
-        CPU.add_thread({
+    for i in 0..HELLO_THREADS {
+        let tid = cpu().add_thread({
             let thread = Thread::new_kernel(hello_thread as usize);
             // 传入一个编号作为参数
             thread.append_initial_arguments([i, 0, 0]);
             thread
         });
+        assert!(set_affinity(tid, boot_hart));
+    }
+
+    // 多核测试：这些线程应当被分配到不同的 hart 上同时运行
+    for i in 0..SMP_THREADS {
+        cpu().add_thread({
+            let thread = Thread::new_kernel(smp_thread as usize);
+            thread.append_initial_arguments([i, 0, 0]);
+            thread
+        });
+    }
+
+    // 条件变量测试：生产者与消费者通过有界缓冲区传递数据，缓冲区空或满时睡眠
+    for i in 0..PC_THREADS {
+        cpu().add_thread({
+            let thread = Thread::new_kernel(producer_thread as usize);
+            thread.append_initial_arguments([i, 0, 0]);
+            thread
+        });
+        cpu().add_thread(Thread::new_kernel(consumer_thread as usize));
+    }
+
+    // CPU 亲和性测试：绑定到一个 hart 后，即使被抢占或让出 CPU 也只在这个 hart 上运行
+    cpu().add_thread(Thread::new_kernel(affinity_thread as usize));
+
+    // join 测试：等待子线程退出并取得退出码，父线程退出后孤儿交给 init
+    cpu().add_thread(Thread::new_kernel(join_thread as usize));
+
+    // 定时睡眠测试：睡眠时间不同的线程按到期的先后被唤醒
+    for &ms in SLEEP_MS.iter() {
+        cpu().add_thread({
+            let thread = Thread::new_kernel(sleep_thread as usize);
+            thread.append_initial_arguments([ms, 0, 0]);
+            thread
+        });
+    }
+
+    // 块设备测试：在线程中读写块设备，请求完成时由中断唤醒
+    if block_device().is_some() {
+        cpu().add_thread(Thread::new_kernel(block_thread as usize));
+    }
+
+    // 优先加载文件系统根目录下的用户程序，没有文件系统时使用链接进内核镜像的用户程序
+    match fs::list("/") {
+        Ok(names) => {
+            for name in names {
+                // 跳过用户程序自己创建的普通文件
+                match fs::read_file(&name) {
+                    Ok(ref data) if data.starts_with(b"\x7fELF") => {
+                        println!("load user program {} from fs", name);
+                        cpu().add_thread(Thread::new_user(data));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Err(_) => {
+            for (name, data) in programs::user_programs().iter() {
+                println!("load user program {}", name);
+                cpu().add_thread(Thread::new_user(data));
+            }
+        }
     }
     println!("++++ setup process!   ++++");
 }
 
+// 初始化当前 hart 的 CPU，每个 hart 都有自己的调度线程 idle
+pub fn init_hart() {
+    // 新建内核线程 idle ，其入口为 Processor::idle_main
+    let idle = Thread::new_kernel(Processor::idle_main as usize);
+    // 我们需要传入 CPU 的地址作为参数
+    idle.append_initial_arguments([cpu() as *const Processor as usize, 0, 0]);
+    let pool = THREAD_POOL
+        .r#try()
+        .expect("thread pool is not initialized!")
+        .clone();
+    // 此后线程可以被放入本 hart 的就绪队列
+    let flags = disable_and_store();
+    pool.lock().set_online(hart_id());
+    restore(flags);
+    cpu().init(idle, pool);
+}
+
+// init 线程：不断回收交给它的子线程，永不退出
+#[no_mangle]
+pub extern "C" fn init_thread() -> ! {
+    loop {
+        wait(None, true);
+    }
+}
+
+const HELLO_THREADS: usize = 5;
+// 已经运行结束的 hello_thread 个数
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
 #[no_mangle]
 pub extern "C" fn hello_thread(arg: usize) -> ! {
     println!("begin of thread {}", arg);
     println!("thread {} is running", arg);
     println!("end  of thread {}", arg);
+    FINISHED.fetch_add(1, Ordering::SeqCst);
     // 通知 CPU 自身已经退出
-    CPU.exit(0);
-    loop {}
+    exit(0);
+}
+
+// 抢占式调度测试：忙等直到所有 hello_thread 结束
+// 若时钟中断不能将其切换出去，其他线程将永远得不到运行
+#[no_mangle]
+pub extern "C" fn spin_thread(total: usize) -> ! {
+    println!("begin of spin thread");
+    while FINISHED.load(Ordering::SeqCst) < total {}
+    println!("preemption test passed: {} threads finished", total);
+    exit(0);
+}
+
+const SMP_THREADS: usize = 8;
+// 运行过 smp_thread 的 hart 集合
+static SMP_HARTS: AtomicUsize = AtomicUsize::new(0);
+static SMP_FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+// 多核测试：每个线程忙等约 0.1 秒，并记录自己在哪些 hart 上运行过
+// 有多个 hart 时，这些线程应当同时在不同的 hart 上运行
+#[no_mangle]
+pub extern "C" fn smp_thread(arg: usize) -> ! {
+    let deadline = get_cycle() + clock_freq() / 10;
+    while get_cycle() < deadline {
+        SMP_HARTS.fetch_or(1 << hart_id(), Ordering::SeqCst);
+    }
+    println!("smp thread {} finished on hart {}", arg, hart_id());
+    if SMP_FINISHED.fetch_add(1, Ordering::SeqCst) + 1 == SMP_THREADS {
+        let harts = SMP_HARTS.load(Ordering::SeqCst);
+        println!("smp test: threads ran on harts {:#b}", harts);
+        if dtb::board().hart_count > 1 {
+            assert!(harts.count_ones() > 1, "smp test failed!");
+        }
+        println!("smp test passed!");
+    }
+    exit(0);
+}
+
+const AFFINITY_ROUNDS: usize = 20;
+
+// 将自己绑定到当前所在的 hart，此后不会被放入其他 hart 的就绪队列，也不会被窃取
+#[no_mangle]
+pub extern "C" fn affinity_thread() -> ! {
+    let hart = hart_id();
+    assert!(set_affinity(current_tid(), 1 << hart));
+    for _ in 0..AFFINITY_ROUNDS {
+        yield_now();
+        assert_eq!(hart_id(), hart, "affinity test failed!");
+    }
+    println!("affinity test passed on hart {}!", hart);
+    exit(0);
+}
+
+// 生产者与消费者的个数，每个生产者产生 PC_ITEMS 个数据，每个消费者取走同样多的数据
+const PC_THREADS: usize = 2;
+const PC_ITEMS: usize = 100;
+// 容量很小，生产者与消费者都会经常睡眠
+const PC_CAPACITY: usize = 4;
+static PC_BUFFER: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static PC_NOT_EMPTY: Condvar = Condvar::new();
+static PC_NOT_FULL: Condvar = Condvar::new();
+// 消费者取走的数据之和，以及已经结束的消费者个数
+static PC_SUM: AtomicUsize = AtomicUsize::new(0);
+static PC_FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+// 生产者 i 产生数据 i * PC_ITEMS + 1 ..= (i + 1) * PC_ITEMS
+#[no_mangle]
+pub extern "C" fn producer_thread(arg: usize) -> ! {
+    for item in arg * PC_ITEMS + 1..=(arg + 1) * PC_ITEMS {
+        let mut buffer = PC_BUFFER.lock();
+        while buffer.len() == PC_CAPACITY {
+            buffer = PC_NOT_FULL.wait(&PC_BUFFER, buffer);
+        }
+        buffer.push(item);
+        drop(buffer);
+        PC_NOT_EMPTY.notify_one();
+    }
+    exit(0);
+}
+
+#[no_mangle]
+pub extern "C" fn consumer_thread() -> ! {
+    let mut sum = 0;
+    for _ in 0..PC_ITEMS {
+        let mut buffer = PC_BUFFER.lock();
+        while buffer.is_empty() {
+            buffer = PC_NOT_EMPTY.wait(&PC_BUFFER, buffer);
+        }
+        sum += buffer.remove(0);
+        drop(buffer);
+        PC_NOT_FULL.notify_one();
+    }
+    PC_SUM.fetch_add(sum, Ordering::SeqCst);
+    if PC_FINISHED.fetch_add(1, Ordering::SeqCst) + 1 == PC_THREADS {
+        let n = PC_THREADS * PC_ITEMS;
+        assert_eq!(
+            PC_SUM.load(Ordering::SeqCst),
+            n * (n + 1) / 2,
+            "producer consumer test failed!"
+        );
+        println!("producer consumer test passed!");
+    }
+    exit(0);
+}
+
+const JOIN_CHILDREN: usize = 3;
+
+// 新建若干退出码不同的子线程，逆序 join 它们
+#[no_mangle]
+pub extern "C" fn join_thread() -> ! {
+    let started = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<JoinHandle> = (0..JOIN_CHILDREN)
+        .map(|i| {
+            let started = started.clone();
+            spawn(move || {
+                started.fetch_add(1, Ordering::SeqCst);
+                timer::sleep(Duration::from_millis(10 * i as u64));
+                100 + i
+            })
+        })
+        .collect();
+    let first = handles[0].tid();
+    for (i, handle) in handles.into_iter().enumerate().rev() {
+        assert_eq!(handle.join(), Some(100 + i), "join test failed!");
+    }
+    assert_eq!(started.load(Ordering::SeqCst), JOIN_CHILDREN);
+    // 已经回收的线程不能再次 join
+    assert_eq!(join(first), None);
+    assert_eq!(join(current_tid()), None);
+    // 孙线程在本线程退出后仍在运行，交给 init 回收
+    let parent = spawn(|| {
+        spawn(|| {
+            timer::sleep(Duration::from_millis(20));
+            7
+        });
+        0
+    });
+    assert_eq!(parent.join(), Some(0), "join test failed!");
+    assert!(match wait(None, false) {
+        WaitStatus::NoChild => true,
+        _ => false,
+    });
+    println!("join test passed!");
+    exit(0);
+}
+
+// 各个 sleep_thread 睡眠的毫秒数
+const SLEEP_MS: [usize; 3] = [300, 100, 200];
+// sleep_thread 被唤醒的顺序
+static SLEEP_ORDER: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+#[no_mangle]
+pub extern "C" fn sleep_thread(ms: usize) -> ! {
+    let start = timer::now_ns();
+    timer::sleep(Duration::from_millis(ms as u64));
+    let elapsed = timer::now_ns() - start;
+    assert!(elapsed >= ms as u64 * 1_000_000, "sleep test failed!");
+    let mut order = SLEEP_ORDER.lock();
+    order.push(ms);
+    if order.len() == SLEEP_MS.len() {
+        assert!(
+            order.windows(2).all(|w| w[0] < w[1]),
+            "sleep test failed: {:?}",
+            *order
+        );
+        println!("sleep test passed: woken in order {:?}", *order);
+    }
+    drop(order);
+    exit(0);
+}
+
+// 读写块设备的倒数第二块，检查写入的数据能被读回，结束后恢复原来的内容
+#[no_mangle]
+pub extern "C" fn block_thread() -> ! {
+    let device = block_device().unwrap();
+    let id = device.block_count() - 2;
+    let mut origin = [0u8; BLOCK_SIZE];
+    let mut buf = [0u8; BLOCK_SIZE];
+    device.read_block(id, &mut origin).unwrap();
+    let data: Vec<u8> = (0..BLOCK_SIZE)
+        .map(|i| (i * 7 + current_tid()) as u8)
+        .collect();
+    device.write_block(id, &data).unwrap();
+    device.read_block(id, &mut buf).unwrap();
+    assert!(buf[..] == data[..], "block thread test failed!");
+    device.write_block(id, &origin).unwrap();
+    println!("block thread test passed!");
+    exit(0);
 }
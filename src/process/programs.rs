@@ -0,0 +1,33 @@
+// 链接进内核镜像的用户程序，由 usr 目录下的用户程序编译得到
+
+// include_bytes! 得到的数组只按字节对齐，而解析 ELF 头需要按 8 字节对齐
+#[repr(align(8))]
+struct Aligned<T: ?Sized>(T);
+
+macro_rules! user_program {
+    ($name:literal) => {{
+        static DATA: &Aligned<[u8]> = &Aligned(*include_bytes!(concat!(
+            "../../usr/target/riscv64imac-unknown-none-elf/debug/",
+            $name
+        )));
+        ($name, &DATA.0)
+    }};
+}
+
+pub fn user_programs() -> [(&'static str, &'static [u8]); 5] {
+    [
+        user_program!("hello_world"),
+        user_program!("fib"),
+        user_program!("stack"),
+        user_program!("syscall_test"),
+        user_program!("fork_test"),
+    ]
+}
+
+// 按名字查找用户程序的 ELF 镜像
+pub fn find(name: &str) -> Option<&'static [u8]> {
+    user_programs()
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, data)| *data)
+}
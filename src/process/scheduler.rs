@@ -1,129 +1,48 @@
 use super::Tid;
-use crate::interrupt::{disable_and_store, enable_and_wfi, restore};
-use crate::process::thread_pool::ThreadPool;
-use crate::process::Thread;
+use crate::context::StackFrame;
+use crate::interrupt::{disable_and_store, enable_and_wfi, restore, timer};
+use crate::process::thread_pool::{ThreadPool, TickAction, WaitStatus};
+use crate::process::{hart_id, Thread};
+use crate::sbi;
 use alloc::boxed::Box;
-use alloc::vec::Vec;
+use alloc::sync::Arc;
 use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::{Mutex, MutexGuard};
 
 #[derive(Clone)]
 pub enum Status {
     Ready,
     Running(Tid),
     Sleeping,
+    // 已经调用 exit，但还没有切换回 idle
     Exited(usize),
+    // 资源已经释放，保留退出码直到被父线程回收
+    Zombie(usize),
 }
 
-pub trait Scheduler {
-    // 如果 tid 不存在，表明将一个新线程加入线程调度
-    // 否则表明一个已有的线程要继续运行
-    fn push(&mut self, tid: Tid);
-    // 从若干可运行线程中选择一个运行
-    fn pop(&mut self) -> Option<Tid>;
-    // 时钟中断中，提醒调度算法当前线程又运行了一个 tick
-    // 返回的 bool 表示调度算法认为当前线程是否需要被切换出去
-    fn tick(&mut self) -> bool;
-    // 告诉调度算法一个线程已经结束
-    fn exit(&mut self, tid: Tid);
-}
-
-#[derive(Default)]
-struct RRInfo {
-    valid: bool,
-    time: usize,
-    prev: usize,
-    next: usize,
-}
+// 在 wfi 中等待的 hart 集合
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
 
-pub struct RRScheduler {
-    threads: Vec<RRInfo>,
-    max_time: usize,
-    current: usize,
+pub fn idle_harts() -> usize {
+    IDLE_HARTS.load(Ordering::SeqCst)
 }
 
-impl RRScheduler {
-    // 设置每个线程连续运行的最大 tick 数
-    pub fn new(max_time_slice: usize) -> Self {
-        let mut rr = RRScheduler {
-            threads: Vec::default(),
-            max_time: max_time_slice,
-            current: 0,
-        };
-        rr.threads.push(RRInfo {
-            valid: false,
-            time: 0,
-            prev: 0,
-            next: 0,
-        });
-        rr
-    }
-}
-
-impl Scheduler for RRScheduler {
-    // 分为 1. 新线程 2. 时间片耗尽被切换出的线程 两种情况
-    fn push(&mut self, tid: Tid) {
-        let tid = tid + 1;
-        if tid + 1 > self.threads.len() {
-            self.threads.resize_with(tid + 1, Default::default);
-        }
-
-        if self.threads[tid].time == 0 {
-            self.threads[tid].time = self.max_time;
-        }
-
-        let prev = self.threads[0].prev;
-        self.threads[tid].valid = true;
-        self.threads[prev].next = tid;
-        self.threads[tid].prev = prev;
-        self.threads[0].prev = tid;
-        self.threads[tid].next = 0;
-    }
-
-    fn pop(&mut self) -> Option<Tid> {
-        let ret = self.threads[0].next;
-        if ret != 0 {
-            let next = self.threads[ret].next;
-            let prev = self.threads[ret].prev;
-            self.threads[next].prev = prev;
-            self.threads[prev].next = next;
-            self.threads[ret].prev = 0;
-            self.threads[ret].next = 0;
-            self.threads[ret].valid = false;
-            self.current = ret;
-            Some(ret - 1)
-        } else {
-            None
-        }
-    }
-
-    // 当前线程的可用时间片 -= 1
-    fn tick(&mut self) -> bool {
-        let tid = self.current;
-        if tid != 0 {
-            self.threads[tid].time -= 1;
-            if self.threads[tid].time == 0 {
-                return true;
-            } else {
-                return false;
-            }
-        }
-        return true;
-    }
-
-    fn exit(&mut self, tid: Tid) {
-        let tid = tid + 1;
-        if self.current == tid {
-            self.current = 0;
-        }
+// 线程被放入 hart 的就绪队列，hart 正在 wfi 中等待时通过 IPI 将其唤醒
+pub fn notify_hart(hart: usize) {
+    if hart != hart_id() && idle_harts() & (1 << hart) != 0 {
+        sbi::send_ipi(1 << hart);
     }
 }
 
 pub struct ProcessorInner {
-    pool: Box<ThreadPool>,
+    // 所有 hart 共享同一个线程池
+    pool: Arc<Mutex<ThreadPool>>,
     idle: Box<Thread>,
     current: Option<(Tid, Box<Thread>)>,
 }
 
+// 每个 hart 拥有一个 Processor，只由该 hart 自己访问
 pub struct Processor {
     inner: UnsafeCell<Option<ProcessorInner>>,
 }
@@ -136,7 +55,7 @@ impl Processor {
         }
     }
 
-    pub fn init(&self, idle: Box<Thread>, pool: Box<ThreadPool>) {
+    pub fn init(&self, idle: Box<Thread>, pool: Arc<Mutex<ThreadPool>>) {
         unsafe {
             *self.inner.get() = Some(ProcessorInner {
                 pool,
@@ -151,9 +70,28 @@ impl Processor {
             .as_mut()
             .expect("Processor is not initialized!")
     }
+    // 在关闭异步中断的情况下访问线程池
+    // 若持有锁时被本 hart 的时钟中断打断，中断处理中再次加锁就会死锁
+    fn with_pool<T>(&self, f: impl FnOnce(&mut ThreadPool) -> T) -> T {
+        let flags = disable_and_store();
+        let ret = f(&mut self.inner().pool.lock());
+        restore(flags);
+        ret
+    }
+
     // 通过线程池新增线程
-    pub fn add_thread(&self, thread: Box<Thread>) {
-        self.inner().pool.add(thread);
+    // 在线程中新增的线程以当前线程为父线程，否则以 init 为父线程
+    pub fn add_thread(&self, thread: Box<Thread>) -> Tid {
+        let parent = self.try_current_tid();
+        self.with_pool(|pool| pool.add(thread, parent))
+    }
+
+    // 复制当前线程并加入线程池
+    pub fn fork(&self, sf: &StackFrame) -> Tid {
+        let (parent, thread) = self.inner().current.as_ref().unwrap();
+        let child = thread.fork(sf);
+        let parent = Some(*parent);
+        self.with_pool(|pool| pool.add(child, parent))
     }
 
     pub fn idle_main(&self) -> ! {
@@ -162,13 +100,16 @@ impl Processor {
         disable_and_store();
         loop {
             // 如果从线程池中获取到一个可运行线程
-            if let Some(thread) = inner.pool.acquire() {
+            if let Some(thread) = self.acquire_or_wait() {
                 // 将自身的正在运行线程设置为刚刚获取到的线程
                 inner.current = Some(thread);
+                // 一个时间片之后触发调度 tick
+                timer::start_tick();
                 // 从正在运行的线程 idle 切换到刚刚获取到的线程
                 println!(
-                    "\n>>>> will switch_to thread {} in idle_main!",
-                    inner.current.as_mut().unwrap().0
+                    "\n>>>> will switch_to thread {} in idle_main of hart {}!",
+                    inner.current.as_mut().unwrap().0,
+                    hart_id()
                 );
                 inner
                     .idle
@@ -179,22 +120,51 @@ impl Processor {
                 // 此时 current 还保存着上个线程
                 let (tid, thread) = inner.current.take().unwrap();
                 // 通知线程池这个线程需要将资源交还出去
-                inner.pool.retrieve(tid, thread);
-            }
-            // 如果现在并无任何可运行线程
-            else {
-                // 打开异步中断，并等待异步中断的到来
-                enable_and_wfi();
-                // 异步中断处理返回后，关闭异步中断
-                disable_and_store();
+                let exited = self.with_pool(|pool| pool.retrieve(tid, thread));
+                // 释放线程的资源 (如管道的一端) 时可能唤醒其他线程，因此在线程池的锁之外进行
+                drop(exited);
             }
         }
     }
+
+    // 从线程池中获取一个可运行线程，本 hart 的就绪队列为空时先从其他 hart 窃取
+    // 没有可运行线程时等待异步中断的到来，中断处理返回后返回 None
+    fn acquire_or_wait(&self) -> Option<(Tid, Box<Thread>)> {
+        let hart = hart_id();
+        if let Some(thread) = self.with_pool(|pool| pool.acquire(hart)) {
+            return Some(thread);
+        }
+        // 标记为空闲之后再检查一次线程池
+        // 此后放入本 hart 就绪队列的线程都会通过 IPI 唤醒本 hart
+        let mask = 1 << hart;
+        IDLE_HARTS.fetch_or(mask, Ordering::SeqCst);
+        let thread = self.with_pool(|pool| pool.acquire(hart));
+        if thread.is_none() {
+            // 空闲时只需要在最早的定时器到期时唤醒
+            timer::clock_set_next_event();
+            // 打开异步中断，并等待异步中断的到来
+            enable_and_wfi();
+            // 异步中断处理返回后，关闭异步中断
+            disable_and_store();
+        }
+        IDLE_HARTS.fetch_and(!mask, Ordering::SeqCst);
+        thread
+    }
     pub fn tick(&self) {
         let inner = self.inner();
         if !inner.current.is_none() {
             // 如果当前有在运行线程
-            if inner.pool.tick() {
+            let tid = inner.current.as_ref().unwrap().0;
+            let switch = match self.with_pool(|pool| pool.tick(tid)) {
+                TickAction::Run => false,
+                TickAction::Switch => true,
+                // 实时线程的预算耗尽，睡眠到下一个周期开始
+                TickAction::Throttle(release) => {
+                    timer::wakeup_at(release, tid);
+                    true
+                }
+            };
+            if switch {
                 // 如果返回true, 表示当前运行线程时间耗尽，需要被调度出去
 
                 // 我们要进入 idle 线程了，因此必须关闭异步中断
@@ -211,14 +181,110 @@ impl Processor {
             }
         }
     }
+    // 当前线程主动放弃 CPU，切换到 idle 线程重新调度
+    pub fn yield_now(&self) {
+        let inner = self.inner();
+        if !inner.current.is_none() {
+            let flags = disable_and_store();
+            inner.current.as_mut().unwrap().1.switch_to(&mut inner.idle);
+            restore(flags);
+        }
+    }
+
+    // 当前线程进入睡眠并切换到 idle，被唤醒后才会再次被调度
+    pub fn sleep<T>(&self, guard: MutexGuard<T>) {
+        let flags = disable_and_store();
+        let inner = self.inner();
+        let tid = inner.current.as_ref().unwrap().0;
+        self.with_pool(|pool| pool.sleep(tid));
+        drop(guard);
+        inner.current.as_mut().unwrap().1.switch_to(&mut inner.idle);
+        restore(flags);
+    }
+
+    pub fn wakeup(&self, tid: Tid) {
+        self.with_pool(|pool| pool.wakeup(tid));
+    }
+
+    pub fn with_current<T>(&self, f: impl FnOnce(&mut Thread) -> T) -> T {
+        f(&mut self.inner().current.as_mut().unwrap().1)
+    }
+
+    // 在当前线程的地址空间中处理缺页异常
+    pub fn handle_page_fault(&self, va: usize) -> bool {
+        match self.inner().current.as_ref() {
+            Some((_, thread)) => match thread.vm.as_ref() {
+                Some(vm) => vm.lock().handle_page_fault(va),
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    pub fn current_tid(&self) -> Tid {
+        self.inner().current.as_ref().unwrap().0
+    }
+
+    // Processor 尚未初始化或没有正在运行的线程时返回 None
+    pub fn try_current_tid(&self) -> Option<Tid> {
+        unsafe { &*self.inner.get() }
+            .as_ref()
+            .and_then(|inner| inner.current.as_ref().map(|(tid, _)| *tid))
+    }
+
+    // tid 是否是当前线程的子线程
+    pub fn is_child(&self, tid: Tid) -> bool {
+        let parent = self.current_tid();
+        self.with_pool(|pool| pool.is_child(parent, tid))
+    }
+
+    pub fn set_priority(&self, tid: Tid, priority: usize) -> bool {
+        self.with_pool(|pool| pool.set_priority(tid, priority))
+    }
+
+    pub fn set_affinity(&self, tid: Tid, affinity: usize) -> bool {
+        self.with_pool(|pool| pool.set_affinity(tid, affinity))
+    }
+
+    pub fn set_realtime(&self, tid: Tid, period: u64, budget: u64) -> bool {
+        self.with_pool(|pool| pool.set_realtime(tid, period, budget))
+    }
+
+    pub fn deadline_misses(&self, tid: Tid) -> Option<usize> {
+        self.with_pool(|pool| pool.deadline_misses(tid))
+    }
+
+    // 等待当前线程的子线程退出并回收，见 ThreadPool::wait
+    pub fn wait(&self, target: Option<Tid>, block: bool) -> WaitStatus {
+        let inner = self.inner();
+        let tid = inner.current.as_ref().unwrap().0;
+        loop {
+            // 标记睡眠之后到切换到 idle 之前不能被时钟中断打断
+            let flags = disable_and_store();
+            let status = self.with_pool(|pool| pool.wait(tid, target, block));
+            match status {
+                WaitStatus::Running if block => {
+                    inner.current.as_mut().unwrap().1.switch_to(&mut inner.idle);
+                    restore(flags);
+                }
+                status => {
+                    restore(flags);
+                    return status;
+                }
+            }
+        }
+    }
+
     pub fn exit(&self, code: usize) -> ! {
         // 由于要切换到 idle 线程，必须先关闭时钟中断
         disable_and_store();
         // 由于自己正在执行，可以通过这种方式获取自身的 tid
         let inner = self.inner();
         let tid = inner.current.as_ref().unwrap().0;
+        // 实时线程可能还有 wakeup_at 设置的定时器
+        timer::cancel(tid);
         // 通知线程池这个线程退出啦！
-        inner.pool.exit(tid);
+        self.with_pool(|pool| pool.exit(tid, code));
         println!("thread {} exited, exit code = {}", tid, code);
 
         // 切换到 idle 线程决定下一个运行哪个线程
@@ -1,32 +1,98 @@
-use super::scheduler::Status;
+use super::scheduler::{idle_harts, notify_hart, Status};
 use super::Thread;
 use super::Tid;
-use crate::process::scheduler::Scheduler;
+use crate::consts::MAX_HARTS;
+use crate::interrupt::timer;
 use alloc::boxed::Box;
 use alloc::vec::Vec;
+use core::cmp;
+use sched::{EdfClass, Scheduler};
 
 struct Task {
     status: Status,
     thread: Option<Box<Thread>>,
+    // 父线程退出后，子线程被交给 init；没有 init 时为 None
+    parent: Option<Tid>,
+    // 是否正在 wait 中睡眠，等待子线程退出
+    waiting: bool,
+    // 可以运行的 hart 集合，第 i 位对应 hart i
+    affinity: usize,
+    // 所在的就绪队列，或者最近一次运行的 hart
+    hart: usize,
+}
+
+impl Task {
+    fn allowed_on(&self, hart: usize) -> bool {
+        self.affinity & (1 << hart) != 0
+    }
+
+    fn is_exited(&self) -> bool {
+        match self.status {
+            Status::Exited(_) | Status::Zombie(_) => true,
+            _ => false,
+        }
+    }
+}
+
+// wait 的结果
+pub enum WaitStatus {
+    // 回收了一个已经退出的子线程及其退出码
+    Exited(Tid, usize),
+    // 有符合条件的子线程，但都还没有退出
+    Running,
+    // 没有符合条件的子线程
+    NoChild,
+}
+
+// tick 的结果
+pub enum TickAction {
+    // 继续运行
+    Run,
+    // 切换出去，之后继续参与调度
+    Switch,
+    // 实时线程的预算耗尽，已被标记为睡眠，应当在给出的 tick 时唤醒
+    Throttle(u64),
 }
 
 pub struct ThreadPool {
     threads: Vec<Option<Task>>,
-    scheduler: Box<dyn Scheduler>,
+    // 实时线程优先于就绪队列中的普通线程，所有 hart 共享
+    edf: EdfClass,
+    // 每个 hart 的就绪队列，以 hartid 为下标
+    // 空闲的 hart 从其他 hart 的就绪队列中窃取线程
+    queues: Vec<Box<dyn Scheduler>>,
+    // 已经参与调度的 hart 集合
+    online: usize,
+    // 回收孤儿线程的 init 线程
+    init: Option<Tid>,
 }
 
 impl ThreadPool {
-    pub fn new(size: usize, scheduler: Box<dyn Scheduler>) -> ThreadPool {
+    // 每个 hart 的就绪队列各使用一个 new_scheduler 新建的调度器
+    pub fn new(size: usize, new_scheduler: impl Fn() -> Box<dyn Scheduler>) -> ThreadPool {
         ThreadPool {
             threads: {
                 let mut v = Vec::new();
                 v.resize_with(size, Default::default);
                 v
             },
-            scheduler,
+            edf: EdfClass::new(),
+            queues: (0..MAX_HARTS).map(|_| new_scheduler()).collect(),
+            online: 0,
+            init: None,
         }
     }
 
+    // hart 开始参与调度，此后线程可以被放入它的就绪队列
+    pub fn set_online(&mut self, hart: usize) {
+        self.online |= 1 << hart;
+    }
+
+    // 此后父线程退出的孤儿以及没有父线程的新线程都交给 init 回收
+    pub fn set_init(&mut self, tid: Tid) {
+        self.init = Some(tid);
+    }
+
     fn alloc_tid(&self) -> Tid {
         for (i, task) in self.threads.iter().enumerate() {
             if task.is_none() {
@@ -36,33 +102,132 @@ impl ThreadPool {
         panic!("no tid to alloc");
     }
 
-    pub fn add(&mut self, _thread: Box<Thread>) {
+    // parent 为 None 时新线程的父线程为 init
+    // 新线程继承父线程的 CPU 亲和性，并优先放在父线程所在的 hart 上
+    pub fn add(&mut self, _thread: Box<Thread>, parent: Option<Tid>) -> Tid {
         let tid = self.alloc_tid();
+        let (affinity, hart) = parent
+            .and_then(|parent| self.threads[parent].as_ref())
+            .map_or((usize::max_value(), 0), |task| (task.affinity, task.hart));
         self.threads[tid] = Some(Task {
             status: Status::Ready,
             thread: Some(_thread),
+            parent: parent.or(self.init),
+            waiting: false,
+            affinity,
+            hart,
         });
-        self.scheduler.push(tid);
+        self.push(tid);
+        tid
+    }
+
+    // 为可以在 affinity 中的 hart 上运行的线程选择一个 hart
+    // 优先选择空闲的 hart，其次是线程上次所在的 hart，否则选择就绪线程最少的 hart
+    fn select_hart(&self, affinity: usize, last: usize) -> usize {
+        let allowed = affinity & self.online;
+        if allowed == 0 {
+            return last;
+        }
+        let idle = idle_harts() & allowed;
+        let candidates = if idle != 0 { idle } else { allowed };
+        if candidates & (1 << last) != 0 {
+            return last;
+        }
+        (0..MAX_HARTS)
+            .filter(|&hart| candidates & (1 << hart) != 0)
+            .min_by_key(|&hart| self.queues[hart].len())
+            .unwrap()
+    }
+
+    // 线程 tid 从 from 的就绪队列转移到 to 的就绪队列
+    // 调度器中的信息 (如时间片、优先级) 随之转移，已经在 from 中等待的线程在 to 中继续等待
+    fn migrate(&mut self, tid: Tid, from: usize, to: usize) {
+        let task = self.threads[tid].as_mut().expect("thread not exist!");
+        if let Some(info) = self.queues[from].take(tid) {
+            self.queues[to].restore(tid, info);
+        }
+        task.hart = to;
     }
 
-    pub fn acquire(&mut self) -> Option<(Tid, Box<Thread>)> {
-        if let Some(tid) = self.scheduler.pop() {
-            let mut task = self.threads[tid].as_mut().expect("thread not exist!");
-            task.status = Status::Running(tid);
-            return Some((tid, task.thread.take().expect("thread not exist!")));
+    // 线程 tid 可以运行了，实时线程交给 EDF 调度类，普通线程放入选出的 hart 的就绪队列
+    // 选出的 hart 空闲时通过 IPI 将其唤醒
+    fn push(&mut self, tid: Tid) {
+        let task = self.threads[tid].as_ref().expect("thread not exist!");
+        let (affinity, last) = (task.affinity, task.hart);
+        let hart = self.select_hart(affinity, last);
+        if self.edf.contains(tid) {
+            self.edf.push(tid, timer::now_ticks());
         } else {
-            return None;
+            if hart != last {
+                self.migrate(tid, last, hart);
+            }
+            self.queues[hart].push(tid);
         }
+        notify_hart(hart);
     }
 
-    pub fn retrieve(&mut self, tid: Tid, thread: Box<Thread>) {
-        // 线程池位置为空，表明这个线程刚刚通过 exit 退出
-        if self.threads[tid].is_none() {
-            // 不需要 CPU 资源了，退出
-            return;
+    // 从就绪线程最多的其他 hart 开始，窃取一个可以在 hart 上运行的线程放入 hart 的就绪队列
+    fn steal(&mut self, hart: usize) -> bool {
+        let mut victims: Vec<usize> = (0..MAX_HARTS)
+            .filter(|&victim| victim != hart && !self.queues[victim].is_empty())
+            .collect();
+        victims.sort_by_key(|&victim| cmp::Reverse(self.queues[victim].len()));
+        for victim in victims {
+            let threads = &self.threads;
+            let tid = self.queues[victim].ready().find(|&tid| {
+                threads[tid]
+                    .as_ref()
+                    .map_or(false, |task| task.allowed_on(hart))
+            });
+            if let Some(tid) = tid {
+                self.migrate(tid, victim, hart);
+                return true;
+            }
         }
+        false
+    }
+
+    // 为 hart 选择一个线程运行
+    // 没有可运行的实时线程时才调度本 hart 就绪队列中的普通线程，就绪队列为空时从其他 hart 窃取
+    pub fn acquire(&mut self, hart: usize) -> Option<(Tid, Box<Thread>)> {
+        let threads = &self.threads;
+        let realtime = self.edf.pop(timer::now_ticks(), |tid| {
+            threads[tid]
+                .as_ref()
+                .map_or(false, |task| task.allowed_on(hart))
+        });
+        let tid = match realtime {
+            Some(tid) => tid,
+            None => {
+                if self.queues[hart].is_empty() {
+                    self.steal(hart);
+                }
+                self.queues[hart].pop()?
+            }
+        };
+        let mut task = self.threads[tid].as_mut().expect("thread not exist!");
+        task.status = Status::Running(tid);
+        task.hart = hart;
+        Some((tid, task.thread.take().expect("thread not exist!")))
+    }
+
+    // 已经退出的线程被交还给调用者释放
+    pub fn retrieve(&mut self, tid: Tid, thread: Box<Thread>) -> Option<Box<Thread>> {
         // 获取并修改线程池对应位置的信息
         let mut thread_info = self.threads[tid].as_mut().expect("thread not exist!");
+        // 线程刚刚通过 exit 退出，直到此时才释放其资源
+        // 否则在它切换回 idle 之前，它的内核栈还在使用中
+        if let Status::Exited(code) = thread_info.status {
+            match thread_info.parent {
+                // 成为僵尸线程，由父线程通过 wait 回收
+                Some(parent) => {
+                    thread_info.status = Status::Zombie(code);
+                    self.notify_parent(parent);
+                }
+                None => self.threads[tid] = None,
+            }
+            return Some(thread);
+        }
         thread_info.thread = Some(thread);
         // 此时状态可能是 Status::Sleeping(线程可能会自动放弃 CPU 资源，进入睡眠状态),
         // 直到被唤醒之前都不必给它分配。
@@ -71,20 +236,195 @@ impl ThreadPool {
             // Running -> Ready
             thread_info.status = Status::Ready;
             // 通知线程池继续给此线程分配资源
-            self.scheduler.push(tid);
+            self.push(tid);
+        }
+        None
+    }
+
+    // 正在运行的线程 tid 进入睡眠，切换回 idle 后不再被加入调度
+    pub fn sleep(&mut self, tid: Tid) {
+        self.threads[tid]
+            .as_mut()
+            .expect("thread not exist!")
+            .status = Status::Sleeping;
+    }
+
+    // 唤醒睡眠中的线程 tid，对其他状态的线程没有影响
+    pub fn wakeup(&mut self, tid: Tid) {
+        if let Some(task) = self.threads[tid].as_mut() {
+            if let Status::Sleeping = task.status {
+                if task.thread.is_some() {
+                    // Sleeping -> Ready
+                    task.status = Status::Ready;
+                    self.push(tid);
+                } else {
+                    // 线程还没有切换回 idle，恢复为 Running，由 retrieve 将其加入调度
+                    task.status = Status::Running(tid);
+                }
+            }
+        }
+    }
+
+    pub fn is_child(&self, parent: Tid, tid: Tid) -> bool {
+        match self.threads.get(tid) {
+            Some(Some(task)) => task.parent == Some(parent),
+            _ => false,
+        }
+    }
+
+    // 设置线程 tid 的优先级，线程不存在时返回 false
+    pub fn set_priority(&mut self, tid: Tid, priority: usize) -> bool {
+        match self.threads.get_mut(tid) {
+            Some(Some(task)) if !task.is_exited() => {
+                self.queues[task.hart].set_priority(tid, priority);
+                true
+            }
+            _ => false,
         }
     }
 
-    // Scheduler 的简单包装：时钟中断时查看当前所运行线程是否要切换出去
-    pub fn tick(&mut self) -> bool {
-        let ret = self.scheduler.tick();
-        ret
+    // 设置线程 tid 可以运行的 hart 集合，第 i 位对应 hart i
+    // 正在其他 hart 上运行的线程在下一次被切换出去后才会转移
+    // 线程不存在或者集合中没有参与调度的 hart 时返回 false
+    pub fn set_affinity(&mut self, tid: Tid, affinity: usize) -> bool {
+        if affinity & self.online == 0 {
+            return false;
+        }
+        let hart = match self.threads.get_mut(tid) {
+            Some(Some(task)) if !task.is_exited() => {
+                task.affinity = affinity;
+                task.hart
+            }
+            _ => return false,
+        };
+        // 在不允许的 hart 的就绪队列中等待，转移到重新选出的 hart
+        if affinity & (1 << hart) == 0 && self.queues[hart].ready().any(|t| t == tid) {
+            let to = self.select_hart(affinity, hart);
+            self.migrate(tid, hart, to);
+            notify_hart(to);
+        }
+        true
+    }
+
+    // 将线程 tid 设为周期为 period、每周期预算为 budget 个 tick 的实时线程
+    // 线程不存在或者接纳后实时线程的总利用率将超过 1 时返回 false
+    pub fn set_realtime(&mut self, tid: Tid, period: u64, budget: u64) -> bool {
+        match self.threads.get(tid) {
+            Some(Some(task)) if !task.is_exited() => {}
+            _ => return false,
+        }
+        let now = timer::now_ticks();
+        if !self.edf.admit(tid, period, budget, now) {
+            return false;
+        }
+        // 已经在就绪队列中等待运行的线程转移到实时调度类
+        let hart = self.threads[tid].as_ref().unwrap().hart;
+        if self.queues[hart].remove(tid) {
+            self.edf.push(tid, now);
+        }
+        true
+    }
+
+    // 线程 tid 错过截止时间的次数，不是实时线程时返回 None
+    pub fn deadline_misses(&self, tid: Tid) -> Option<usize> {
+        self.edf.misses(tid)
+    }
+
+    // 时钟中断时查看 hart 上所运行线程 tid 是否要切换出去
+    // 实时线程按预算决定，普通线程由 Scheduler 决定，有截止时间更早的实时线程等待时也要让出
+    pub fn tick(&mut self, tid: Tid) -> TickAction {
+        let now = timer::now_ticks();
+        let hart = self.threads[tid].as_ref().expect("thread not exist!").hart;
+        if self.edf.contains(tid) {
+            if let Some(release) = self.edf.tick(tid, now) {
+                self.sleep(tid);
+                return TickAction::Throttle(release);
+            }
+        } else if self.queues[hart].tick(tid) {
+            return TickAction::Switch;
+        }
+        let threads = &self.threads;
+        if self.edf.should_preempt(tid, now, |tid| {
+            threads[tid]
+                .as_ref()
+                .map_or(false, |task| task.allowed_on(hart))
+        }) {
+            TickAction::Switch
+        } else {
+            TickAction::Run
+        }
     }
     // 这个线程已经退出了，线程状态 Running -> Exited
-    pub fn exit(&mut self, tid: Tid) {
-        // 清空线程池对应位置
-        self.threads[tid] = None;
+    // 在 retrieve 时成为僵尸线程，被父线程回收后才清空线程池对应位置
+    pub fn exit(&mut self, tid: Tid, code: usize) {
+        let task = self.threads[tid].as_mut().expect("thread not exist!");
+        task.status = Status::Exited(code);
+        let hart = task.hart;
         // 通知调度器
-        self.scheduler.exit(tid);
+        self.edf.exit(tid);
+        self.queues[hart].exit(tid);
+        // 子线程交给 init，init 自身退出时子线程不再有父线程
+        let init = self.init.filter(|&init| init != tid);
+        let mut zombie = false;
+        for task in self.threads.iter_mut().filter_map(|task| task.as_mut()) {
+            if task.parent == Some(tid) {
+                task.parent = init;
+                if let Status::Zombie(_) = task.status {
+                    zombie = true;
+                }
+            }
+        }
+        if let Some(init) = init {
+            if zombie {
+                self.notify_parent(init);
+            }
+        }
+    }
+
+    // 唤醒正在 wait 的父线程
+    fn notify_parent(&mut self, parent: Tid) {
+        if let Some(task) = self.threads[parent].as_mut() {
+            if task.waiting {
+                task.waiting = false;
+                self.wakeup(parent);
+            }
+        }
+    }
+
+    // 回收 parent 的子线程 target，target 为 None 时回收任意一个已经退出的子线程
+    // 子线程都还没有退出时，若 block 为 true，parent 进入睡眠，直到有子线程退出
+    // init 总是有可能收到新的孤儿，因此即使没有子线程也会睡眠
+    pub fn wait(&mut self, parent: Tid, target: Option<Tid>, block: bool) -> WaitStatus {
+        let mut found = false;
+        let mut zombie = None;
+        for (tid, task) in self.threads.iter().enumerate() {
+            let task = match task {
+                Some(task) if task.parent == Some(parent) => task,
+                _ => continue,
+            };
+            if target.map_or(false, |target| target != tid) {
+                continue;
+            }
+            found = true;
+            if let Status::Zombie(code) = task.status {
+                zombie = Some((tid, code));
+                break;
+            }
+        }
+        if let Some((tid, code)) = zombie {
+            self.threads[tid] = None;
+            return WaitStatus::Exited(tid, code);
+        }
+        if !found && (target.is_some() || self.init != Some(parent)) {
+            return WaitStatus::NoChild;
+        }
+        if block {
+            self.sleep(parent);
+            self.threads[parent]
+                .as_mut()
+                .expect("thread not exist!")
+                .waiting = true;
+        }
+        WaitStatus::Running
     }
 }
@@ -1,5 +1,9 @@
 #![allow(dead_code)]
 
+use crate::consts::PHYSICAL_MEMORY_OFFSET;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// legacy 调用，a7 为调用编号
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
@@ -10,8 +14,72 @@ const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
 const SBI_SHUTDOWN: usize = 8;
 
+// SBI v0.2 起的扩展编号
+const EXT_BASE: usize = 0x10;
+const EXT_TIME: usize = 0x54494D45;
+const EXT_IPI: usize = 0x735049;
+const EXT_RFENCE: usize = 0x52464E43;
+const EXT_HSM: usize = 0x48534D;
+const EXT_SRST: usize = 0x53525354;
+const EXT_DBCN: usize = 0x4442434E;
+
+// Base 扩展的功能编号
+const BASE_GET_SPEC_VERSION: usize = 0;
+const BASE_GET_IMPL_ID: usize = 1;
+const BASE_GET_IMPL_VERSION: usize = 2;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+// 错误码
+pub const SBI_SUCCESS: isize = 0;
+pub const SBI_ERR_FAILED: isize = -1;
+pub const SBI_ERR_NOT_SUPPORTED: isize = -2;
+pub const SBI_ERR_INVALID_PARAM: isize = -3;
+pub const SBI_ERR_DENIED: isize = -4;
+pub const SBI_ERR_INVALID_ADDRESS: isize = -5;
+pub const SBI_ERR_ALREADY_AVAILABLE: isize = -6;
+
+// system_reset 的类型与原因
+pub const RESET_TYPE_SHUTDOWN: usize = 0;
+pub const RESET_TYPE_COLD_REBOOT: usize = 1;
+pub const RESET_TYPE_WARM_REBOOT: usize = 2;
+pub const RESET_REASON_NONE: usize = 0;
+pub const RESET_REASON_SYSTEM_FAILURE: usize = 1;
+
+// hart_get_status 返回的状态
+pub const HART_STARTED: usize = 0;
+pub const HART_STOPPED: usize = 1;
+pub const HART_START_PENDING: usize = 2;
+pub const HART_STOP_PENDING: usize = 3;
+
+// 扩展调用的返回值：a0 为错误码，a1 为返回值
+#[derive(Debug, Clone, Copy)]
+pub struct SbiRet {
+    pub error: isize,
+    pub value: usize,
+}
+
+impl SbiRet {
+    pub fn is_ok(&self) -> bool {
+        self.error == SBI_SUCCESS
+    }
+}
+
 #[inline(always)]
-fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
+fn sbi_call(eid: usize, fid: usize, args: [usize; 5]) -> SbiRet {
+    let (error, value);
+    unsafe {
+        llvm_asm!("ecall"
+            : "={x10}" (error), "={x11}" (value)
+            : "{x10}" (args[0]), "{x11}" (args[1]), "{x12}" (args[2]), "{x13}" (args[3]),
+              "{x14}" (args[4]), "{x16}" (fid), "{x17}" (eid)
+            : "memory"
+            : "volatile");
+    }
+    SbiRet { error, value }
+}
+
+#[inline(always)]
+fn sbi_call_legacy(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let ret;
     unsafe {
         llvm_asm!("ecall"
@@ -23,52 +91,230 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+// init 探测到的扩展，探测之前一律使用 legacy 调用
+const HAS_TIME: usize = 1 << 0;
+const HAS_IPI: usize = 1 << 1;
+const HAS_RFENCE: usize = 1 << 2;
+const HAS_HSM: usize = 1 << 3;
+const HAS_SRST: usize = 1 << 4;
+const HAS_DBCN: usize = 1 << 5;
+static EXTENSIONS: AtomicUsize = AtomicUsize::new(0);
+
+fn has(ext: usize) -> bool {
+    EXTENSIONS.load(Ordering::Relaxed) & ext != 0
+}
+
+// 探测 SBI 实现支持的扩展，只需在启动 hart 上调用一次
+pub fn init() {
+    let version = spec_version();
+    // v0.1 的实现没有 Base 扩展，只能使用 legacy 调用
+    if version.is_ok() && version.value != 0 {
+        let mut extensions = 0;
+        for &(eid, flag) in [
+            (EXT_TIME, HAS_TIME),
+            (EXT_IPI, HAS_IPI),
+            (EXT_RFENCE, HAS_RFENCE),
+            (EXT_HSM, HAS_HSM),
+            (EXT_SRST, HAS_SRST),
+            (EXT_DBCN, HAS_DBCN),
+        ]
+        .iter()
+        {
+            if probe_extension(eid) {
+                extensions |= flag;
+            }
+        }
+        EXTENSIONS.store(extensions, Ordering::Relaxed);
+        println!(
+            "sbi v{}.{}, impl id {}, impl version {:#x}, extensions {:#b}",
+            version.value >> 24 & 0x7f,
+            version.value & 0xffffff,
+            impl_id().value,
+            impl_version().value,
+            extensions
+        );
+    } else {
+        println!("sbi v0.1, using legacy calls");
+    }
+}
+
+// ---------------- Base ----------------
+
+// value 的第 24~30 位为主版本号，低 24 位为次版本号
+pub fn spec_version() -> SbiRet {
+    sbi_call(EXT_BASE, BASE_GET_SPEC_VERSION, [0; 5])
+}
+
+pub fn impl_id() -> SbiRet {
+    sbi_call(EXT_BASE, BASE_GET_IMPL_ID, [0; 5])
+}
+
+pub fn impl_version() -> SbiRet {
+    sbi_call(EXT_BASE, BASE_GET_IMPL_VERSION, [0; 5])
+}
+
+pub fn probe_extension(eid: usize) -> bool {
+    let ret = sbi_call(EXT_BASE, BASE_PROBE_EXTENSION, [eid, 0, 0, 0, 0]);
+    ret.is_ok() && ret.value != 0
+}
+
+// ---------------- TIME ----------------
+
 pub fn set_timer(stime_value: u64) {
+    if has(HAS_TIME) {
+        sbi_call(EXT_TIME, 0, [stime_value as usize, 0, 0, 0, 0]);
+        return;
+    }
     #[cfg(target_pointer_width = "32")]
-    sbi_call(
+    sbi_call_legacy(
         SBI_SET_TIMER,
         stime_value as usize,
         (stime_value >> 32) as usize,
         0,
     );
     #[cfg(target_pointer_width = "64")]
-    sbi_call(SBI_SET_TIMER, stime_value as usize, 0, 0);
+    sbi_call_legacy(SBI_SET_TIMER, stime_value as usize, 0, 0);
+}
+
+// ---------------- DBCN ----------------
+
+// 调试控制台要求缓冲区的物理地址
+// 内核镜像与物理内存的线性映射偏移相同，因此内核中的地址都可以这样转换
+fn console_buffer(buf: *const u8) -> usize {
+    buf as usize - PHYSICAL_MEMORY_OFFSET
 }
 
 pub fn console_putchar(ch: usize) {
-    sbi_call(SBI_CONSOLE_PUTCHAR, ch, 0, 0);
+    if has(HAS_DBCN) {
+        sbi_call(EXT_DBCN, 2, [ch & 0xff, 0, 0, 0, 0]);
+    } else {
+        sbi_call_legacy(SBI_CONSOLE_PUTCHAR, ch, 0, 0);
+    }
 }
 
+// 没有输入时返回 usize::max_value()
 pub fn console_getchar() -> usize {
-    sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
+    if has(HAS_DBCN) {
+        let mut buf = [0u8; 1];
+        let ret = sbi_call(EXT_DBCN, 1, [1, console_buffer(buf.as_mut_ptr()), 0, 0, 0]);
+        if ret.is_ok() && ret.value == 1 {
+            buf[0] as usize
+        } else {
+            usize::max_value()
+        }
+    } else {
+        sbi_call_legacy(SBI_CONSOLE_GETCHAR, 0, 0, 0)
+    }
 }
 
+// 写入 buf，返回实际写入的字节数
+pub fn console_write(buf: &[u8]) -> usize {
+    if has(HAS_DBCN) {
+        let ret = sbi_call(
+            EXT_DBCN,
+            0,
+            [buf.len(), console_buffer(buf.as_ptr()), 0, 0, 0],
+        );
+        if ret.is_ok() {
+            ret.value
+        } else {
+            0
+        }
+    } else {
+        for &ch in buf {
+            console_putchar(ch as usize);
+        }
+        buf.len()
+    }
+}
+
+// ---------------- IPI ----------------
+
 pub fn clear_ipi() {
-    sbi_call(SBI_CLEAR_IPI, 0, 0, 0);
+    if has(HAS_IPI) {
+        // v0.2 起由 S 态自行清除 sip 的 SSIP 位
+        unsafe {
+            llvm_asm!("csrci sip, 1 << 1" :::: "volatile");
+        }
+    } else {
+        sbi_call_legacy(SBI_CLEAR_IPI, 0, 0, 0);
+    }
 }
 
+// hart_mask 的第 i 位对应 hartid 为 i 的 hart
 pub fn send_ipi(hart_mask: usize) {
-    sbi_call(SBI_SEND_IPI, &hart_mask as *const _ as usize, 0, 0);
+    if has(HAS_IPI) {
+        sbi_call(EXT_IPI, 0, [hart_mask, 0, 0, 0, 0]);
+    } else {
+        sbi_call_legacy(SBI_SEND_IPI, &hart_mask as *const _ as usize, 0, 0);
+    }
 }
 
+// ---------------- RFENCE ----------------
+
 pub fn remote_fence_i(hart_mask: usize) {
-    sbi_call(SBI_REMOTE_FENCE_I, &hart_mask as *const _ as usize, 0, 0);
+    if has(HAS_RFENCE) {
+        sbi_call(EXT_RFENCE, 0, [hart_mask, 0, 0, 0, 0]);
+    } else {
+        sbi_call_legacy(SBI_REMOTE_FENCE_I, &hart_mask as *const _ as usize, 0, 0);
+    }
 }
 
-pub fn remote_sfence_vma(hart_mask: usize, _start: usize, _size: usize) {
-    sbi_call(SBI_REMOTE_SFENCE_VMA, &hart_mask as *const _ as usize, 0, 0);
+// 刷新 hart_mask 中各 hart 上 [start, start + size) 的地址转换缓存
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) {
+    if has(HAS_RFENCE) {
+        sbi_call(EXT_RFENCE, 1, [hart_mask, 0, start, size, 0]);
+    } else {
+        sbi_call_legacy(
+            SBI_REMOTE_SFENCE_VMA,
+            &hart_mask as *const _ as usize,
+            start,
+            size,
+        );
+    }
 }
 
-pub fn remote_sfence_vma_asid(hart_mask: usize, _start: usize, _size: usize, _asid: usize) {
-    sbi_call(
-        SBI_REMOTE_SFENCE_VMA_ASID,
-        &hart_mask as *const _ as usize,
-        0,
-        0,
-    );
+pub fn remote_sfence_vma_asid(hart_mask: usize, start: usize, size: usize, asid: usize) {
+    if has(HAS_RFENCE) {
+        sbi_call(EXT_RFENCE, 2, [hart_mask, 0, start, size, asid]);
+    } else {
+        sbi_call_legacy(
+            SBI_REMOTE_SFENCE_VMA_ASID,
+            &hart_mask as *const _ as usize,
+            0,
+            0,
+        );
+    }
+}
+
+// ---------------- HSM ----------------
+
+// 让 hartid 从物理地址 start_addr 开始以 S 态运行，a0 = hartid, a1 = opaque
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> SbiRet {
+    sbi_call(EXT_HSM, 0, [hartid, start_addr, opaque, 0, 0])
+}
+
+// 停止当前 hart，成功时不返回
+pub fn hart_stop() -> SbiRet {
+    sbi_call(EXT_HSM, 1, [0; 5])
+}
+
+// 成功时 value 为 HART_STARTED 等状态
+pub fn hart_get_status(hartid: usize) -> SbiRet {
+    sbi_call(EXT_HSM, 2, [hartid, 0, 0, 0, 0])
+}
+
+// ---------------- SRST ----------------
+
+// 成功时不返回
+pub fn system_reset(reset_type: usize, reason: usize) -> SbiRet {
+    sbi_call(EXT_SRST, 0, [reset_type, reason, 0, 0, 0])
 }
 
 pub fn shutdown() -> ! {
-    sbi_call(SBI_SHUTDOWN, 0, 0, 0);
+    if has(HAS_SRST) {
+        system_reset(RESET_TYPE_SHUTDOWN, RESET_REASON_NONE);
+    }
+    sbi_call_legacy(SBI_SHUTDOWN, 0, 0, 0);
     unreachable!()
 }
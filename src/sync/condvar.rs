@@ -0,0 +1,30 @@
+use super::WaitQueue;
+use spin::{Mutex, MutexGuard};
+
+// 条件变量：等待由 mutex 保护的条件成立
+pub struct Condvar {
+    queue: WaitQueue,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Condvar {
+            queue: WaitQueue::new(),
+        }
+    }
+
+    // 释放 guard 并睡眠，被唤醒后重新获取 mutex 的锁
+    // 被唤醒时条件未必成立，调用者应当在循环中重新检查
+    pub fn wait<'a, T>(&self, mutex: &'a Mutex<T>, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.queue.sleep(guard);
+        mutex.lock()
+    }
+
+    pub fn notify_one(&self) -> bool {
+        self.queue.wake_one()
+    }
+
+    pub fn notify_all(&self) -> usize {
+        self.queue.wake_all()
+    }
+}
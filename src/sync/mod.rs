@@ -0,0 +1,6 @@
+// 线程的阻塞与唤醒
+mod condvar;
+mod wait_queue;
+
+pub use condvar::Condvar;
+pub use wait_queue::WaitQueue;
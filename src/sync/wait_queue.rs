@@ -0,0 +1,61 @@
+use crate::interrupt::{disable_and_store, restore};
+use crate::process::{self, Tid};
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+
+// 在某个条件上睡眠的线程，按睡眠的先后顺序唤醒
+// 可以在中断处理中唤醒，因此访问队列时关闭异步中断
+pub struct WaitQueue {
+    waiters: Mutex<Vec<Tid>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        WaitQueue {
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    // 当前线程加入队列并睡眠，直到被 wake_one 或 wake_all 唤醒
+    // guard 保护着线程等待的条件，在标记睡眠之后才被释放，因此不会错过唤醒
+    // 若中断处理中也会获取 guard 对应的锁，加锁前必须关闭异步中断
+    pub fn sleep<T>(&self, guard: MutexGuard<T>) {
+        let flags = disable_and_store();
+        self.waiters.lock().push(process::current_tid());
+        process::sleep(guard);
+        restore(flags);
+    }
+
+    // 唤醒最早睡眠的线程，队列为空时返回 false
+    pub fn wake_one(&self) -> bool {
+        let flags = disable_and_store();
+        let mut waiters = self.waiters.lock();
+        let tid = if waiters.is_empty() {
+            None
+        } else {
+            Some(waiters.remove(0))
+        };
+        drop(waiters);
+        restore(flags);
+        match tid {
+            Some(tid) => {
+                process::wakeup(tid);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 唤醒所有线程，返回唤醒的个数
+    pub fn wake_all(&self) -> usize {
+        let flags = disable_and_store();
+        let mut waiters = self.waiters.lock();
+        let count = waiters.len();
+        for tid in waiters.drain(..) {
+            process::wakeup(tid);
+        }
+        drop(waiters);
+        restore(flags);
+        count
+    }
+}
@@ -0,0 +1,357 @@
+use crate::consts::*;
+use crate::context::StackFrame;
+use crate::fs::{
+    self,
+    file::File,
+    pipe::make_pipe,
+    vfs::{FsError, FsResult, Stat},
+};
+use crate::interrupt::timer;
+use crate::process::{self, WaitStatus};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+// 系统调用编号，与 Linux RISC-V 保持一致
+pub const SYS_DUP: usize = 23;
+pub const SYS_OPENAT: usize = 56;
+pub const SYS_CLOSE: usize = 57;
+pub const SYS_PIPE2: usize = 59;
+pub const SYS_LSEEK: usize = 62;
+pub const SYS_READ: usize = 63;
+pub const SYS_WRITE: usize = 64;
+pub const SYS_FSTAT: usize = 80;
+pub const SYS_EXIT: usize = 93;
+pub const SYS_NANOSLEEP: usize = 101;
+pub const SYS_CLOCK_GETTIME: usize = 113;
+pub const SYS_SCHED_YIELD: usize = 124;
+pub const SYS_SETPRIORITY: usize = 140;
+pub const SYS_GETPID: usize = 172;
+// RISC-V 上 Linux 没有 fork，这里借用 clone 的编号实现 fork 语义
+pub const SYS_CLONE: usize = 220;
+pub const SYS_WAIT4: usize = 260;
+
+// 错误码，返回时取负
+const EPERM: isize = 1;
+const ENOENT: isize = 2;
+const ESRCH: isize = 3;
+const EIO: isize = 5;
+const EBADF: isize = 9;
+const ECHILD: isize = 10;
+const EFAULT: isize = 14;
+const EEXIST: isize = 17;
+const ENOTDIR: isize = 20;
+const EISDIR: isize = 21;
+const EINVAL: isize = 22;
+const EMFILE: isize = 24;
+const EFBIG: isize = 27;
+const ENOSPC: isize = 28;
+const ESPIPE: isize = 29;
+const EPIPE: isize = 32;
+const ENAMETOOLONG: isize = 36;
+const ENOSYS: isize = 38;
+
+// 路径的最大长度，包括结尾的 '\0'
+const PATH_MAX: usize = 256;
+
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+// setpriority 的 which
+const PRIO_PROCESS: usize = 0;
+
+// nice 值的范围，越小得到的 CPU 时间越多
+const MIN_NICE: isize = -20;
+const MAX_NICE: isize = 19;
+
+// nice 值 -20..=19 对应的优先级，取自 Linux 的 sched_prio_to_weight
+// nice 值每差 1，得到的 CPU 时间约差 10%，nice 值为 0 时与步长调度的默认优先级相同
+const NICE_TO_PRIORITY: [usize; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+// wait4 的 options：子进程都还没有退出时立即返回 0
+const WNOHANG: usize = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+// 系统调用入口
+// a7 为系统调用编号，a0 ~ a5 为参数，返回值写回 a0
+pub fn syscall(sf: &mut StackFrame) {
+    // ecall 指令长度为 4 字节，返回用户态时执行下一条指令
+    sf.sepc += 4;
+    let id = sf.reg[17];
+    let args = [
+        sf.reg[10], sf.reg[11], sf.reg[12], sf.reg[13], sf.reg[14], sf.reg[15],
+    ];
+    let ret = match id {
+        SYS_DUP => sys_dup(args[0]),
+        SYS_OPENAT => sys_openat(args[0], args[1] as *const u8, args[2]),
+        SYS_CLOSE => sys_close(args[0]),
+        SYS_PIPE2 => sys_pipe(args[0] as *mut [usize; 2]),
+        SYS_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYS_READ => sys_read(args[0], args[1] as *mut u8, args[2]),
+        SYS_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYS_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYS_EXIT => sys_exit(args[0]),
+        SYS_NANOSLEEP => sys_nanosleep(args[0] as *const TimeSpec),
+        SYS_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut TimeSpec),
+        SYS_SCHED_YIELD => sys_yield(),
+        SYS_SETPRIORITY => sys_setpriority(args[0], args[1], args[2] as isize),
+        SYS_GETPID => sys_getpid(),
+        SYS_CLONE => sys_fork(sf),
+        SYS_WAIT4 => sys_wait4(args[0] as isize, args[1] as *mut i32, args[2]),
+        _ => {
+            println!("unknown syscall id {}, args = {:x?}", id, args);
+            -ENOSYS
+        }
+    };
+    sf.reg[10] = ret as usize;
+}
+
+// 检查 [ptr, ptr + len) 是否全部位于当前线程地址空间中用户态可以访问的区域
+// 内核要写入这段内存时 write 为 true，此时还要求区域可写
+fn check_user_buffer(ptr: usize, len: usize, write: bool) -> bool {
+    let end = match ptr.checked_add(len) {
+        Some(end) if ptr != 0 => end,
+        _ => return false,
+    };
+    process::with_current(|thread| {
+        thread
+            .vm
+            .as_ref()
+            .map_or(false, |vm| vm.lock().check_user_range(ptr, end, write))
+    })
+}
+
+// 读入用户态以 '\0' 结尾的字符串
+fn user_str(ptr: *const u8) -> Result<String, isize> {
+    let mut bytes = Vec::new();
+    loop {
+        if bytes.len() == PATH_MAX {
+            return Err(-ENAMETOOLONG);
+        }
+        let addr = ptr as usize + bytes.len();
+        // 同一页中的其余字节不必重复检查
+        if (bytes.is_empty() || addr % PAGE_SIZE == 0) && !check_user_buffer(addr, 1, false) {
+            return Err(-EFAULT);
+        }
+        match unsafe { *(addr as *const u8) } {
+            0 => break,
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| -EINVAL)
+}
+
+fn errno(e: FsError) -> isize {
+    -match e {
+        FsError::NotFound => ENOENT,
+        FsError::NotDir => ENOTDIR,
+        FsError::IsDir => EISDIR,
+        FsError::Exists => EEXIST,
+        FsError::BadFd => EBADF,
+        FsError::TooManyFiles => EMFILE,
+        FsError::NotSeekable => ESPIPE,
+        FsError::BrokenPipe => EPIPE,
+        FsError::InvalidParam => EINVAL,
+        FsError::NoSpace => ENOSPC,
+        FsError::FileTooLarge => EFBIG,
+        FsError::NotSupported => ENOSYS,
+        FsError::Io => EIO,
+        FsError::NameTooLong => ENAMETOOLONG,
+    }
+}
+
+fn fs_ret(ret: FsResult<usize>) -> isize {
+    match ret {
+        Ok(n) => n as isize,
+        Err(e) => errno(e),
+    }
+}
+
+// 当前线程的文件描述符 fd 对应的文件
+fn file_of(fd: usize) -> FsResult<Arc<File>> {
+    process::with_current(|thread| thread.files.get(fd))
+}
+
+fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    if len == 0 {
+        return 0;
+    }
+    if !check_user_buffer(buf as usize, len, true) {
+        return -EFAULT;
+    }
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+    fs_ret(file_of(fd).and_then(|file| file.read(buf)))
+}
+
+fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    if len == 0 {
+        return 0;
+    }
+    if !check_user_buffer(buf as usize, len, false) {
+        return -EFAULT;
+    }
+    let buf = unsafe { core::slice::from_raw_parts(buf, len) };
+    fs_ret(file_of(fd).and_then(|file| file.write(buf)))
+}
+
+// 只支持从根目录开始的路径，忽略 dirfd
+fn sys_openat(_dirfd: usize, path: *const u8, flags: usize) -> isize {
+    let path = match user_str(path) {
+        Ok(path) => path,
+        Err(e) => return e,
+    };
+    fs_ret(
+        fs::open(&path, flags)
+            .and_then(|file| process::with_current(|thread| thread.files.add(file))),
+    )
+}
+
+fn sys_close(fd: usize) -> isize {
+    fs_ret(process::with_current(|thread| thread.files.close(fd)).map(|_| 0))
+}
+
+fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    fs_ret(file_of(fd).and_then(|file| file.seek(offset, whence)))
+}
+
+fn sys_dup(fd: usize) -> isize {
+    fs_ret(process::with_current(|thread| thread.files.dup(fd)))
+}
+
+// fds[0] 为读端，fds[1] 为写端
+fn sys_pipe(fds: *mut [usize; 2]) -> isize {
+    if !check_user_buffer(fds as usize, core::mem::size_of::<[usize; 2]>(), true) {
+        return -EFAULT;
+    }
+    let (reader, writer) = make_pipe();
+    let ret = process::with_current(|thread| {
+        let read_fd = thread.files.add(reader)?;
+        match thread.files.add(writer) {
+            Ok(write_fd) => Ok([read_fd, write_fd]),
+            Err(e) => {
+                thread.files.close(read_fd)?;
+                Err(e)
+            }
+        }
+    });
+    fs_ret(ret.map(|pair| {
+        unsafe {
+            *fds = pair;
+        }
+        0
+    }))
+}
+
+fn sys_fstat(fd: usize, stat: *mut Stat) -> isize {
+    if !check_user_buffer(stat as usize, core::mem::size_of::<Stat>(), true) {
+        return -EFAULT;
+    }
+    fs_ret(file_of(fd).and_then(|file| file.stat()).map(|s| {
+        unsafe {
+            *stat = s;
+        }
+        0
+    }))
+}
+
+fn sys_exit(code: usize) -> isize {
+    process::exit(code);
+}
+
+fn sys_yield() -> isize {
+    process::yield_now();
+    0
+}
+
+// 只支持 which 为 PRIO_PROCESS，who 为 0 时表示当前进程
+// 与 Linux 相同，prio 为 nice 值，超出范围时取最近的边界
+fn sys_setpriority(which: usize, who: usize, prio: isize) -> isize {
+    if which != PRIO_PROCESS {
+        return -EINVAL;
+    }
+    let nice = prio.max(MIN_NICE).min(MAX_NICE);
+    let tid = if who == 0 {
+        process::current_tid()
+    } else {
+        who
+    };
+    // 只能设置自己及自己的子进程
+    if tid != process::current_tid() && !process::is_child(tid) {
+        return -EPERM;
+    }
+    if process::set_priority(tid, NICE_TO_PRIORITY[(nice - MIN_NICE) as usize]) {
+        0
+    } else {
+        -ESRCH
+    }
+}
+
+fn sys_getpid() -> isize {
+    process::current_tid() as isize
+}
+
+// 父线程返回子线程的 tid，子线程返回 0
+fn sys_fork(sf: &StackFrame) -> isize {
+    process::fork(sf) as isize
+}
+
+// pid 为 -1 时等待任意子进程，返回被回收的子进程的 pid
+// status 不为空时按 Linux 的格式写入子进程的退出状态，退出码在 8..16 位
+fn sys_wait4(pid: isize, status: *mut i32, options: usize) -> isize {
+    let target = match pid {
+        -1 => None,
+        pid if pid > 0 => Some(pid as usize),
+        _ => return -EINVAL,
+    };
+    if !status.is_null() && !check_user_buffer(status as usize, core::mem::size_of::<i32>(), true) {
+        return -EFAULT;
+    }
+    match process::wait(target, options & WNOHANG == 0) {
+        WaitStatus::Exited(tid, code) => {
+            if !status.is_null() {
+                unsafe {
+                    *status = ((code & 0xff) << 8) as i32;
+                }
+            }
+            tid as isize
+        }
+        WaitStatus::Running => 0,
+        WaitStatus::NoChild => -ECHILD,
+    }
+}
+
+fn sys_nanosleep(req: *const TimeSpec) -> isize {
+    if !check_user_buffer(req as usize, core::mem::size_of::<TimeSpec>(), false) {
+        return -EFAULT;
+    }
+    let req = unsafe { *req };
+    if req.nsec as u64 >= NSEC_PER_SEC {
+        return -EINVAL;
+    }
+    timer::sleep(Duration::new(req.sec as u64, req.nsec as u32));
+    0
+}
+
+fn sys_clock_gettime(_clock_id: usize, tp: *mut TimeSpec) -> isize {
+    if !check_user_buffer(tp as usize, core::mem::size_of::<TimeSpec>(), true) {
+        return -EFAULT;
+    }
+    let now = timer::now_ns();
+    unsafe {
+        *tp = TimeSpec {
+            sec: (now / NSEC_PER_SEC) as usize,
+            nsec: (now % NSEC_PER_SEC) as usize,
+        };
+    }
+    0
+}
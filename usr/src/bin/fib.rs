@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+// 放在 .bss 中，检验用户程序的 .bss 已被清零
+static mut RESULT: [usize; 30] = [0; 30];
+
+#[no_mangle]
+pub fn main() -> usize {
+    unsafe {
+        RESULT[1] = 1;
+        for i in 2..30 {
+            RESULT[i] = RESULT[i - 1] + RESULT[i - 2];
+        }
+        println!("fib(29) = {}", RESULT[29]);
+        // fib(29) = 514229
+        assert_eq!(RESULT[29], 514229);
+    }
+    0
+}
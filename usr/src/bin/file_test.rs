@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::io::STDOUT;
+use user::syscall::*;
+
+#[no_mangle]
+pub fn main() -> usize {
+    // 文件不存在时新建，存在时清空
+    let fd = sys_open("/file_test.txt\0", O_CREAT | O_TRUNC | O_RDWR);
+    assert!(fd >= 3, "open failed: {}", fd);
+    let fd = fd as usize;
+    let data = b"hello, easy-fs!";
+    assert_eq!(sys_write(fd, data), data.len() as isize);
+
+    // dup 得到的文件描述符共享偏移，写入接在后面
+    let fd2 = sys_dup(fd) as usize;
+    assert_eq!(sys_write(fd2, b"!!"), 2);
+    assert_eq!(sys_close(fd2), 0);
+    assert!(sys_write(fd2, b"?") < 0);
+
+    let mut stat = Stat::default();
+    assert_eq!(sys_fstat(fd, &mut stat), 0);
+    assert_eq!(stat.mode & S_IFMT, S_IFREG);
+    assert_eq!(stat.size as usize, data.len() + 2);
+
+    // 回到开头读出写入的内容
+    assert_eq!(sys_lseek(fd, 0, SEEK_SET), 0);
+    let mut buf = [0u8; 32];
+    let len = sys_read(fd, &mut buf) as usize;
+    assert_eq!(&buf[..len], b"hello, easy-fs!!!");
+    assert_eq!(sys_read(fd, &mut buf), 0);
+    assert_eq!(sys_close(fd), 0);
+
+    // 重新打开，文件内容已经写入磁盘
+    let fd = sys_open("/file_test.txt\0", O_RDONLY) as usize;
+    assert_eq!(sys_lseek(fd, -3, SEEK_END), 14);
+    assert_eq!(sys_read(fd, &mut buf), 3);
+    assert!(sys_write(fd, b"x") < 0);
+    assert_eq!(sys_close(fd), 0);
+    assert!(sys_open("/no_such_file\0", O_RDONLY) < 0);
+
+    // 超过 27 字节的文件名不会被截断后新建，每次都返回 -ENAMETOOLONG
+    let long = "/a_file_name_longer_than_the_limit\0";
+    assert_eq!(sys_open(long, O_CREAT | O_RDWR), -36);
+    assert_eq!(sys_open(long, O_CREAT | O_EXCL | O_RDWR), -36);
+
+    // 控制台既可以通过 /dev/console 打开，也可以通过默认的 0, 1, 2 访问
+    let console = sys_open("/dev/console\0", O_WRONLY);
+    assert!(console >= 0);
+    sys_write(console as usize, b"file_test: hello from /dev/console\n");
+    assert_eq!(sys_fstat(STDOUT, &mut stat), 0);
+    assert_eq!(stat.mode & S_IFMT, S_IFCHR);
+    assert!(sys_lseek(STDOUT, 0, SEEK_SET) < 0);
+    sys_close(console as usize);
+
+    println!("file_test passed!");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::syscall::{sleep, sys_exit, sys_fork, sys_getpid};
+
+// 位于 .data 段，fork 后父子进程各自写入，检验写时复制
+static mut SHARED: usize = 1;
+
+#[no_mangle]
+pub fn main() -> usize {
+    // 栈上的数据同样被写时复制
+    let mut local = 10;
+    let pid = sys_fork();
+    if pid == 0 {
+        unsafe {
+            SHARED = 2;
+        }
+        local += 1;
+        println!(
+            "fork_test: child {} sees shared = {}, local = {}",
+            sys_getpid(),
+            unsafe { SHARED },
+            local
+        );
+        assert_eq!(local, 11);
+        sys_exit(0);
+    }
+    // 等待子进程写完
+    sleep(50);
+    let shared = unsafe { SHARED };
+    println!(
+        "fork_test: parent forked child {}, shared = {}, local = {}",
+        pid, shared, local
+    );
+    assert_eq!(shared, 1);
+    assert_eq!(local, 10);
+    println!("fork_test passed!");
+    0
+}
@@ -0,0 +1,15 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+#[no_mangle]
+pub fn main() -> usize {
+    // 先执行一条 ebreak，证明确实运行在 U 态并能够陷入内核
+    user::ebreak();
+    for _ in 0..10 {
+        println!("Hello world! from user mode program!");
+    }
+    0
+}
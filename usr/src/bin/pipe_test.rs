@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::syscall::{sys_close, sys_exit, sys_fork, sys_pipe, sys_read, sys_write};
+
+// 远大于管道缓冲区，写者必然会因缓冲区已满而睡眠
+const TOTAL: usize = 8192;
+
+fn byte(i: usize) -> u8 {
+    (i * 7 % 251) as u8
+}
+
+#[no_mangle]
+pub fn main() -> usize {
+    let mut fds = [0usize; 2];
+    assert_eq!(sys_pipe(&mut fds), 0);
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+    assert!(sys_write(read_fd, b"x") < 0);
+
+    if sys_fork() == 0 {
+        // 子进程只写，关闭自己的读端
+        sys_close(read_fd);
+        let mut chunk = [0u8; 100];
+        let mut sent = 0;
+        while sent < TOTAL {
+            let len = chunk.len().min(TOTAL - sent);
+            for (i, b) in chunk[..len].iter_mut().enumerate() {
+                *b = byte(sent + i);
+            }
+            assert_eq!(sys_write(write_fd, &chunk[..len]), len as isize);
+            sent += len;
+        }
+        // 退出时关闭写端，父进程随后读到 EOF
+        sys_exit(0);
+    }
+
+    // 父进程必须关闭自己的写端，否则永远读不到 EOF
+    sys_close(write_fd);
+    let mut buf = [0u8; 64];
+    let mut received = 0;
+    loop {
+        let len = sys_read(read_fd, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        for &b in buf[..len as usize].iter() {
+            assert_eq!(b, byte(received));
+            received += 1;
+        }
+    }
+    assert_eq!(received, TOTAL);
+    sys_close(read_fd);
+
+    // 读端全部关闭后写入失败
+    assert_eq!(sys_pipe(&mut fds), 0);
+    sys_close(fds[0]);
+    assert!(sys_write(fds[1], b"x") < 0);
+    sys_close(fds[1]);
+    println!("pipe_test passed!");
+    0
+}
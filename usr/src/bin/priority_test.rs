@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::syscall::{sys_exit, sys_fork, sys_set_priority, sys_waitpid, sys_yield, wexitstatus};
+
+#[no_mangle]
+pub fn main() -> usize {
+    // nice 值超出范围时取最近的边界
+    assert_eq!(sys_set_priority(0, -100), 0);
+    assert_eq!(sys_set_priority(0, -5), 0);
+    let pid = sys_fork();
+    if pid == 0 {
+        for _ in 0..3 {
+            sys_yield();
+        }
+        sys_exit(7);
+    }
+    // 可以设置子进程的 nice 值
+    assert_eq!(sys_set_priority(pid as usize, 10), 0);
+    let mut status = -1;
+    assert_eq!(sys_waitpid(pid, &mut status, 0), pid);
+    assert_eq!(wexitstatus(status), 7);
+    // 子进程已被回收
+    assert!(sys_set_priority(pid as usize, 10) < 0);
+    println!("priority_test passed!");
+    0
+}
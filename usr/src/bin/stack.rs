@@ -0,0 +1,23 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+// 递归占用若干页用户栈
+fn depth(n: usize) -> usize {
+    let buf = [n; 64];
+    if n == 0 {
+        0
+    } else {
+        depth(n - 1) + buf[n % 64] - n + 1
+    }
+}
+
+#[no_mangle]
+pub fn main() -> usize {
+    let d = depth(200);
+    println!("recursion depth = {}", d);
+    assert_eq!(d, 200);
+    0
+}
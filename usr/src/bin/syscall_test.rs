@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::io::{STDIN, STDOUT};
+use user::syscall::{get_time, sleep, sys_getpid, sys_read, sys_write, sys_yield};
+
+#[no_mangle]
+pub fn main() -> usize {
+    let pid = sys_getpid();
+    println!("syscall_test: pid = {}", pid);
+    for _ in 0..3 {
+        sys_yield();
+    }
+    // 与 Linux 一致，长度为 0 的读写直接返回 0，不检查缓冲区地址
+    assert_eq!(sys_write(STDOUT, &[]), 0);
+    assert_eq!(sys_read(STDIN, &mut []), 0);
+    let start = get_time();
+    sleep(100);
+    let elapsed = get_time() - start;
+    println!("syscall_test: slept {} ms", elapsed);
+    assert!(elapsed >= 100);
+    println!("syscall_test passed!");
+    0
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::syscall::{sleep, sys_exit, sys_fork, sys_waitpid, wexitstatus, WNOHANG};
+
+const CHILDREN: usize = 4;
+
+#[no_mangle]
+pub fn main() -> usize {
+    let mut pids = [0isize; CHILDREN];
+    for (i, pid) in pids.iter_mut().enumerate() {
+        *pid = sys_fork();
+        if *pid == 0 {
+            // 晚创建的子进程先退出
+            sleep(10 * (CHILDREN - i));
+            sys_exit(20 + i);
+        }
+    }
+    let mut status = 0;
+    // 子进程都还在睡眠
+    assert_eq!(sys_waitpid(-1, &mut status, WNOHANG), 0);
+    // 等待指定的子进程
+    assert_eq!(sys_waitpid(pids[0], &mut status, 0), pids[0]);
+    assert_eq!(wexitstatus(status), 20);
+    // 其余子进程此时都已退出，按任意顺序回收
+    let mut reaped = 1;
+    loop {
+        let pid = sys_waitpid(-1, &mut status, 0);
+        if pid < 0 {
+            break;
+        }
+        let i = pids.iter().position(|&p| p == pid).expect("unknown child");
+        assert_eq!(wexitstatus(status), 20 + i as i32);
+        reaped += 1;
+    }
+    assert_eq!(reaped, CHILDREN);
+    // 已经回收的子进程不能再等待
+    assert!(sys_waitpid(pids[1], &mut status, 0) < 0);
+    println!("wait_test passed!");
+    0
+}
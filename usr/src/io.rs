@@ -0,0 +1,44 @@
+use crate::syscall::{sys_read, sys_write};
+use core::fmt::{self, Write};
+
+pub const STDIN: usize = 0;
+pub const STDOUT: usize = 1;
+
+pub fn putchar(ch: char) {
+    sys_write(STDOUT, &[ch as u8]);
+}
+
+pub fn puts(s: &str) {
+    sys_write(STDOUT, s.as_bytes());
+}
+
+pub fn getchar() -> u8 {
+    let mut c = [0u8; 1];
+    sys_read(STDIN, &mut c);
+    c[0]
+}
+
+struct Stdout;
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        puts(s);
+        Ok(())
+    }
+}
+
+pub fn _print(args: fmt::Arguments) {
+    Stdout.write_fmt(args).unwrap();
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ({
+        $crate::io::_print(format_args!($($arg)*));
+    });
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
+}
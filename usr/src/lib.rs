@@ -0,0 +1,39 @@
+#![no_std]
+#![feature(llvm_asm)]
+
+#[macro_use]
+pub mod io;
+
+pub mod syscall;
+
+use core::panic::PanicInfo;
+use syscall::sys_exit;
+
+extern "C" {
+    fn main() -> usize;
+}
+
+#[no_mangle]
+#[link_section = ".text.entry"]
+pub extern "C" fn _start() -> ! {
+    let code = unsafe { main() };
+    sys_exit(code);
+}
+
+// 触发一次断点异常，内核会打印出断点的位置并返回
+pub fn ebreak() {
+    unsafe {
+        llvm_asm!("ebreak" :::: "volatile");
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("{}", info);
+    sys_exit(1);
+}
+
+#[no_mangle]
+extern "C" fn abort() -> ! {
+    panic!("abort!");
+}
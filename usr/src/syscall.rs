@@ -0,0 +1,170 @@
+// 系统调用编号，与内核及 Linux RISC-V 保持一致
+const SYS_DUP: usize = 23;
+const SYS_OPENAT: usize = 56;
+const SYS_CLOSE: usize = 57;
+const SYS_PIPE2: usize = 59;
+const SYS_LSEEK: usize = 62;
+const SYS_READ: usize = 63;
+const SYS_WRITE: usize = 64;
+const SYS_FSTAT: usize = 80;
+const SYS_EXIT: usize = 93;
+const SYS_NANOSLEEP: usize = 101;
+const SYS_CLOCK_GETTIME: usize = 113;
+const SYS_SCHED_YIELD: usize = 124;
+const SYS_SETPRIORITY: usize = 140;
+const SYS_GETPID: usize = 172;
+const SYS_CLONE: usize = 220;
+const SYS_WAIT4: usize = 260;
+
+// sys_waitpid 的 options：子进程都还没有退出时立即返回 0
+pub const WNOHANG: usize = 1;
+
+// openat 的 dirfd，内核只支持从根目录开始的路径
+const AT_FDCWD: isize = -100;
+
+// open 的 flags
+pub const O_RDONLY: usize = 0;
+pub const O_WRONLY: usize = 1;
+pub const O_RDWR: usize = 2;
+pub const O_CREAT: usize = 0o100;
+pub const O_EXCL: usize = 0o200;
+pub const O_TRUNC: usize = 0o1000;
+pub const O_APPEND: usize = 0o2000;
+
+// lseek 的 whence
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+// Stat::mode 中的文件类型
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFIFO: u32 = 0o010000;
+
+// 与内核中的定义一致
+#[repr(C)]
+#[derive(Default)]
+pub struct Stat {
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub size: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+#[inline(always)]
+fn sys_call(id: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
+    let ret: isize;
+    unsafe {
+        llvm_asm!("ecall"
+            : "={x10}" (ret)
+            : "{x17}" (id), "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2)
+            : "memory"
+            : "volatile");
+    }
+    ret
+}
+
+pub fn sys_read(fd: usize, buf: &mut [u8]) -> isize {
+    sys_call(SYS_READ, fd, buf.as_mut_ptr() as usize, buf.len())
+}
+
+pub fn sys_write(fd: usize, buf: &[u8]) -> isize {
+    sys_call(SYS_WRITE, fd, buf.as_ptr() as usize, buf.len())
+}
+
+// path 必须以 '\0' 结尾
+pub fn sys_open(path: &str, flags: usize) -> isize {
+    sys_call(SYS_OPENAT, AT_FDCWD as usize, path.as_ptr() as usize, flags)
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    sys_call(SYS_CLOSE, fd, 0, 0)
+}
+
+// 返回新的偏移
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_call(SYS_LSEEK, fd, offset as usize, whence)
+}
+
+// fds[0] 为读端，fds[1] 为写端
+pub fn sys_pipe(fds: &mut [usize; 2]) -> isize {
+    sys_call(SYS_PIPE2, fds.as_mut_ptr() as usize, 0, 0)
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    sys_call(SYS_DUP, fd, 0, 0)
+}
+
+pub fn sys_fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_call(SYS_FSTAT, fd, stat as *mut Stat as usize, 0)
+}
+
+pub fn sys_exit(code: usize) -> ! {
+    sys_call(SYS_EXIT, code, 0, 0);
+    loop {}
+}
+
+pub fn sys_nanosleep(req: &TimeSpec) -> isize {
+    sys_call(SYS_NANOSLEEP, req as *const TimeSpec as usize, 0, 0)
+}
+
+pub fn sys_clock_gettime(tp: &mut TimeSpec) -> isize {
+    sys_call(SYS_CLOCK_GETTIME, 0, tp as *mut TimeSpec as usize, 0)
+}
+
+pub fn sys_yield() -> isize {
+    sys_call(SYS_SCHED_YIELD, 0, 0, 0)
+}
+
+// 设置进程 pid 的 nice 值，pid 为 0 时为当前进程
+// nice 的范围为 -20..=19，越小得到的 CPU 时间越多，超出范围时取最近的边界
+pub fn sys_set_priority(pid: usize, nice: isize) -> isize {
+    sys_call(SYS_SETPRIORITY, 0, pid, nice as usize)
+}
+
+pub fn sys_getpid() -> isize {
+    sys_call(SYS_GETPID, 0, 0, 0)
+}
+
+// 父进程中返回子进程的 pid，子进程中返回 0
+pub fn sys_fork() -> isize {
+    sys_call(SYS_CLONE, 0, 0, 0)
+}
+
+// pid 为 -1 时等待任意子进程，返回被回收的子进程的 pid，其退出状态写入 status
+pub fn sys_waitpid(pid: isize, status: &mut i32, options: usize) -> isize {
+    sys_call(
+        SYS_WAIT4,
+        pid as usize,
+        status as *mut i32 as usize,
+        options,
+    )
+}
+
+// 从 sys_waitpid 得到的退出状态中取出退出码的低 8 位
+pub fn wexitstatus(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+
+// 以毫秒为单位的当前时间
+pub fn get_time() -> usize {
+    let mut tp = TimeSpec::default();
+    sys_clock_gettime(&mut tp);
+    tp.sec * 1000 + tp.nsec / 1_000_000
+}
+
+pub fn sleep(ms: usize) {
+    sys_nanosleep(&TimeSpec {
+        sec: ms / 1000,
+        nsec: ms % 1000 * 1_000_000,
+    });
+}